@@ -0,0 +1,185 @@
+// derive macros, enabled with the `derive` feature
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Error, Field, Fields, Result};
+
+// `Display` with the `#[ex_display("...")]` template of the struct or of each variant
+pub fn ex_display(input: &DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = write_templates(input, "ex_display")?;
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn fmt(&self, __ex_f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                #body
+            }
+        }
+    })
+}
+
+// `Display` with the `#[ex_error("...")]` templates, and `Error` with the `#[source]` or
+// `#[from]` field as source, `#[from]` fields also get a `From` impl
+pub fn ex_error(input: &DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = write_templates(input, "ex_error")?;
+
+    let mut sources = Vec::new();
+    let mut froms = Vec::new();
+
+    for case in cases(input)? {
+        let pattern = case.pattern();
+        let source = match source_field(case.fields)? {
+            Some((i, field)) => {
+                let binding = binding(i, field);
+                if field.attrs.iter().any(|attr| attr.path().is_ident("from")) {
+                    froms.push(from_impl(input, &case, i, field)?);
+                }
+                quote!(::std::option::Option::Some((*#binding).as_dyn_error()))
+            }
+            None => quote!(::std::option::Option::None),
+        };
+        sources.push(quote!(#pattern => #source,));
+    }
+
+    let sources = match &input.data {
+        Data::Enum(data) if data.variants.is_empty() => quote!(match *self {}),
+        _ => quote!(match self { #(#sources)* }),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn fmt(&self, __ex_f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                #body
+            }
+        }
+
+        impl #impl_generics ::std::error::Error for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                use ::expression_format::__private::AsDynError as _;
+                #sources
+            }
+        }
+
+        #(#froms)*
+    })
+}
+
+// the struct, or a variant of the enum
+struct Case<'a> {
+    path: TokenStream,
+    ident: &'a Ident,
+    attrs: &'a [Attribute],
+    fields: &'a Fields,
+}
+
+impl Case<'_> {
+    // binds the fields by name, or as `_0`, `_1`, ... for tuple fields
+    fn pattern(&self) -> TokenStream {
+        let path = &self.path;
+        let names = self.fields.iter().enumerate().map(|(i, field)| binding(i, field));
+        match self.fields {
+            Fields::Named(_) => quote!(#path { #(#names),* }),
+            Fields::Unnamed(_) => quote!(#path(#(#names),*)),
+            Fields::Unit => quote!(#path),
+        }
+    }
+}
+
+fn cases(input: &DeriveInput) -> Result<Vec<Case<'_>>> {
+    match &input.data {
+        Data::Struct(data) => Ok(vec![Case {
+            path: quote!(Self),
+            ident: &input.ident,
+            attrs: &input.attrs,
+            fields: &data.fields,
+        }]),
+        Data::Enum(data) => Ok(data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                Case { path: quote!(Self::#ident), ident, attrs: &variant.attrs, fields: &variant.fields }
+            })
+            .collect()),
+        Data::Union(_) => Err(Error::new_spanned(&input.ident, "unions aren't supported")),
+    }
+}
+
+fn binding(i: usize, field: &Field) -> Ident {
+    field.ident.clone().unwrap_or_else(|| format_ident!("_{}", i))
+}
+
+// matches `self` and writes the template of the struct or variant into `__ex_f`
+fn write_templates(input: &DeriveInput, attr: &str) -> Result<TokenStream> {
+    if let Data::Enum(data) = &input.data {
+        if data.variants.is_empty() {
+            return Ok(quote!(match *self {}));
+        }
+    }
+
+    let mut arms = Vec::new();
+    for case in cases(input)? {
+        let template = case
+            .attrs
+            .iter()
+            .find(|found| found.path().is_ident(attr))
+            .ok_or_else(|| Error::new_spanned(case.ident, format!("missing `#[{}(\"...\")]` attribute", attr)))?;
+
+        let tokens = template.meta.require_list()?.tokens.clone();
+        let generate = |tokens: &str| crate::ex_impl_with_args("::std::write", &["__ex_f".to_string()], tokens, None);
+        // `proc_macro` can't be used by the unit tests
+        let write: TokenStream = if proc_macro::is_available() {
+            crate::span::expand(tokens.into(), 0, |tokens| generate(&tokens.to_string())).into()
+        } else {
+            generate(&tokens.to_string()).parse()?
+        };
+        let pattern = case.pattern();
+        arms.push(quote!(#pattern => #write,));
+    }
+
+    Ok(quote!(match self { #(#arms)* }))
+}
+
+// the field with `#[source]` or `#[from]`, or else the field named `source`
+fn source_field(fields: &Fields) -> Result<Option<(usize, &Field)>> {
+    let is_source = |attr: &Attribute| attr.path().is_ident("source") || attr.path().is_ident("from");
+    let marked: Vec<_> = fields.iter().enumerate().filter(|(_, field)| field.attrs.iter().any(is_source)).collect();
+
+    match &marked[..] {
+        [] => Ok(fields.iter().enumerate().find(|(_, field)| matches!(&field.ident, Some(ident) if ident == "source"))),
+        [source] => Ok(Some(*source)),
+        [_, (_, field), ..] => Err(Error::new_spanned(field, "only one field can be the source")),
+    }
+}
+
+fn from_impl(input: &DeriveInput, case: &Case, i: usize, field: &Field) -> Result<TokenStream> {
+    if case.fields.len() > 1 {
+        return Err(Error::new_spanned(field, "`#[from]` must be the only field"));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let path = &case.path;
+    let ty = &field.ty;
+    let value = match &field.ident {
+        Some(ident) => quote!(#path { #ident: source }),
+        None => {
+            let index = syn::Index::from(i);
+            quote!(#path { #index: source })
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#ty> for #name #ty_generics #where_clause {
+            fn from(source: #ty) -> Self {
+                #value
+            }
+        }
+    })
+}
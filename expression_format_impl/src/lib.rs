@@ -0,0 +1,1945 @@
+//! Crate with macro implementations of `expression_format`.
+//!
+//! A separete crate is required to test procedural macros.
+
+#![cfg_attr(feature = "nightly", feature(proc_macro_span))]
+
+extern crate proc_macro;
+
+use proc_macro::{Spacing, TokenStream, TokenTree};
+use expression_format_syntax as syntax;
+use regex::Regex;
+use std::ops::Range;
+
+#[cfg(feature = "derive")]
+mod derive;
+mod span;
+
+// =====================================================================
+// public
+// =====================================================================
+
+#[proc_macro]
+pub fn ex_format(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("format", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_print(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("print", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_println(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("println", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_eprint(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("eprint", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_eprintln(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("eprintln", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn try_ex_print(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| try_print_impl("stdout", false, &item.to_string()))
+}
+
+#[proc_macro]
+pub fn try_ex_println(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| try_print_impl("stdout", true, &item.to_string()))
+}
+
+#[proc_macro]
+pub fn try_ex_eprint(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| try_print_impl("stderr", false, &item.to_string()))
+}
+
+#[proc_macro]
+pub fn try_ex_eprintln(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| try_print_impl("stderr", true, &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_formatdoc(item: TokenStream) -> TokenStream {
+    span::expand_dedented(item, 0, |item| ex_impl("format", &dedent_template(&item.to_string())))
+}
+
+#[proc_macro]
+pub fn ex_printdoc(item: TokenStream) -> TokenStream {
+    span::expand_dedented(item, 0, |item| ex_impl("print", &dedent_template(&item.to_string())))
+}
+
+#[proc_macro]
+pub fn ex_eprintdoc(item: TokenStream) -> TokenStream {
+    span::expand_dedented(item, 0, |item| ex_impl("eprint", &dedent_template(&item.to_string())))
+}
+
+#[proc_macro]
+pub fn try_ex_printdoc(item: TokenStream) -> TokenStream {
+    span::expand_dedented(item, 0, |item| try_print_impl("stdout", false, &dedent_template(&item.to_string())))
+}
+
+#[proc_macro]
+pub fn try_ex_eprintdoc(item: TokenStream) -> TokenStream {
+    span::expand_dedented(item, 0, |item| try_print_impl("stderr", false, &dedent_template(&item.to_string())))
+}
+
+#[proc_macro]
+pub fn ex_writedoc(item: TokenStream) -> TokenStream {
+    span::expand_dedented(item, 1, |item| {
+        let (args, arg) = split_args(item, 1);
+        ex_impl_with_args("write", &args, &dedent_template(&arg), None)
+    })
+}
+
+#[proc_macro]
+pub fn ex_format_args(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("::std::format_args", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_display(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_display_impl(&item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_html(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_html_impl(&item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_url(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_url_impl(&item.to_string(), false))
+}
+
+#[proc_macro]
+pub fn ex_url_parse(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_url_impl(&item.to_string(), true))
+}
+
+#[proc_macro]
+pub fn ex_sql(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| {
+        let (args, arg) = split_sql_args(item);
+        ex_sql_impl(&args, &arg)
+    })
+}
+
+#[proc_macro]
+pub fn ex_cmd(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_cmd_impl(&item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_scan(item: TokenStream) -> TokenStream {
+    span::expand(item, 1, |item| {
+        let (args, arg) = split_args(item, 1);
+        ex_scan_impl(&args, &arg)
+    })
+}
+
+#[proc_macro]
+pub fn ex_write(item: TokenStream) -> TokenStream {
+    span::expand(item, 1, |item| {
+        let (args, arg) = split_args(item, 1);
+        ex_impl_with_args("write", &args, &arg, None)
+    })
+}
+
+#[proc_macro]
+pub fn ex_writeln(item: TokenStream) -> TokenStream {
+    span::expand(item, 1, |item| {
+        let (args, arg) = split_args(item, 1);
+        ex_impl_with_args("writeln", &args, &arg, None)
+    })
+}
+
+#[proc_macro]
+pub fn ex_panic(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_message_impl("::std::panic", &[], &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_unreachable(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_message_impl("::std::unreachable", &[], &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_todo(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_message_impl("::std::todo", &[], &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_unimplemented(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_message_impl("::std::unimplemented", &[], &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_assert(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::std::assert", item, 1)
+}
+
+#[proc_macro]
+pub fn ex_assert_eq(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::std::assert_eq", item, 2)
+}
+
+#[proc_macro]
+pub fn ex_assert_ne(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::std::assert_ne", item, 2)
+}
+
+#[proc_macro]
+pub fn ex_debug_assert(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::std::debug_assert", item, 1)
+}
+
+#[proc_macro]
+pub fn ex_debug_assert_eq(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::std::debug_assert_eq", item, 2)
+}
+
+#[proc_macro]
+pub fn ex_debug_assert_ne(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::std::debug_assert_ne", item, 2)
+}
+
+#[proc_macro]
+pub fn ex_log(item: TokenStream) -> TokenStream {
+    ex_log_impl("log", item, 1)
+}
+
+#[proc_macro]
+pub fn ex_error(item: TokenStream) -> TokenStream {
+    ex_log_impl("error", item, 0)
+}
+
+#[proc_macro]
+pub fn ex_warn(item: TokenStream) -> TokenStream {
+    ex_log_impl("warn", item, 0)
+}
+
+#[proc_macro]
+pub fn ex_info(item: TokenStream) -> TokenStream {
+    ex_log_impl("info", item, 0)
+}
+
+#[proc_macro]
+pub fn ex_debug(item: TokenStream) -> TokenStream {
+    ex_log_impl("debug", item, 0)
+}
+
+#[proc_macro]
+pub fn ex_trace(item: TokenStream) -> TokenStream {
+    ex_log_impl("trace", item, 0)
+}
+
+#[proc_macro]
+pub fn ex_event(item: TokenStream) -> TokenStream {
+    span::expand(item, 1, |item| {
+        let (args, arg) = split_log_args(item, 1);
+        ex_event_impl(&args, &arg)
+    })
+}
+
+#[proc_macro]
+pub fn ex_anyhow(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("::expression_format::__private::anyhow::anyhow", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_bail(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| ex_impl("::expression_format::__private::anyhow::bail", &item.to_string()))
+}
+
+#[proc_macro]
+pub fn ex_ensure(item: TokenStream) -> TokenStream {
+    ex_assert_impl("::expression_format::__private::anyhow::ensure", item, 1)
+}
+
+#[proc_macro]
+pub fn ex_with_context(item: TokenStream) -> TokenStream {
+    span::expand(item, 1, |item| {
+        let (args, arg) = split_args(item, 1);
+        ex_with_context_impl(&args, &arg)
+    })
+}
+
+#[proc_macro]
+pub fn ex_dbg(item: TokenStream) -> TokenStream {
+    span::expand(item, 0, |item| {
+        let (args, value) = split_args(item, 1);
+        ex_dbg_impl(&args, &value)
+    })
+}
+
+#[cfg(feature = "derive")]
+#[proc_macro_derive(ExDisplay, attributes(ex_display))]
+pub fn derive_ex_display(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    derive::ex_display(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[cfg(feature = "derive")]
+#[proc_macro_derive(ExError, attributes(ex_error, source, from))]
+pub fn derive_ex_error(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    derive::ex_error(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+// =====================================================================
+// private
+// =====================================================================
+
+// splits the first `count` comma separated arguments from the rest of `item`, the commas between
+// generic arguments like in `HashMap::<u8, u8>::new()` or `<T as Trait<A, B>>::f()` don't split
+// them, while a `<` elsewhere is a comparison
+fn split_args(item: TokenStream, count: usize) -> (Vec<String>, String) {
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let is_punct = |i: usize, c: char| matches!(tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == c);
+    let mut args = Vec::with_capacity(count);
+    let mut start = 0;
+    let mut angles = 0;
+
+    for i in 0..tokens.len() {
+        if args.len() == count {
+            break;
+        }
+
+        if is_punct(i, ',') && angles == 0 {
+            args.push(tokens[start..i].iter().cloned().collect::<TokenStream>().to_string());
+            start = i + 1;
+        } else if is_punct(i, '<') && (i == start || angles > 0 || (i >= 2 && is_punct(i - 1, ':') && is_punct(i - 2, ':'))) {
+            angles += 1;
+        } else if is_punct(i, '>') && angles > 0 && !(i > 0 && is_punct(i - 1, '-')) {
+            angles -= 1;
+        }
+    }
+
+    (args, tokens[start..].iter().cloned().collect::<TokenStream>().to_string())
+}
+
+fn ex_impl(func: &str, arg: &str) -> String {
+    ex_impl_with_args(func, &[], arg, None)
+}
+
+// `args` are passed to `func` before the format string, `arg` is the string literal optionally
+// followed by positional and named arguments like in `format!`
+fn ex_impl_with_args(func: &str, args: &[String], arg: &str, empty: Option<&str>) -> String {
+    let (arg, trailing) = split_template(arg);
+    let template = match parse_template(arg, &trailing, empty) {
+        Ok(template) => template,
+        Err(error) => return error,
+    };
+    let mut ex_args = args.to_vec();
+    ex_args.push(span::mark(&template.fmt, 0..arg.len()));
+    ex_args.extend(template.exprs.iter().map(Expr::code));
+    ex_args.extend(trailing);
+
+    // width and precision expressions are passed as named arguments after the other ones
+    for (i, count) in template.counts.into_iter().enumerate() {
+        ex_args.push(format!("{}={}", count_name(i), count));
+    }
+
+    format!("{}!({})", func, ex_args.join(","))
+}
+
+struct Template {
+    // format string with the embedded expressions removed
+    fmt: String,
+    exprs: Vec<Expr>,
+    // expressions for the `*` width and precision parameters
+    counts: Vec<String>,
+}
+
+struct Expr {
+    // format spec as understood by `format!`
+    spec: String,
+    text: String,
+    // ranges in the template of the text, of the spec and of the whole placeholder
+    span: Range<usize>,
+    spec_span: Range<usize>,
+    placeholder: Range<usize>,
+}
+
+impl Expr {
+    // the text in the generated code
+    fn code(&self) -> String {
+        span::mark(&self.text, self.span.clone())
+    }
+
+    // `part` at the end of the text in the generated code, like `expr` in `{!expr}`
+    fn code_of(&self, part: &str) -> String {
+        match self.text.rfind(part) {
+            Some(start) => span::mark(part, self.span.start + start..self.span.start + start + part.len()),
+            None => part.to_string(),
+        }
+    }
+}
+
+enum Segment {
+    // text as written in the string literal, including its quotes and `{{` and `}}` escapes
+    Text(String),
+    Expr(Expr),
+}
+
+// splits the string literal `arg` into text and placeholders, the text before a self documenting
+// `{=expr}` ends with `expr=`, also returns the `*` width and precision expressions, `named` are
+// the names of the named arguments after the template
+fn parse_segments(arg: &str, named: &[String]) -> (Vec<Segment>, Vec<String>) {
+    let mut segments = Vec::new();
+    let mut counts = Vec::new();
+
+    let parsed = syntax::parse(arg);
+    // positional arguments come after the embedded expressions, `N$` counts are shifted past them
+    let embedded = parsed
+        .iter()
+        .filter(|segment| match segment {
+            syntax::Segment::Placeholder(placeholder) => is_embedded(arg[placeholder.expr.clone()].trim(), named),
+            syntax::Segment::Text(_) => false,
+        })
+        .count();
+
+    for segment in parsed {
+        let placeholder = match segment {
+            syntax::Segment::Text(span) => {
+                segments.push(Segment::Text(arg[span].to_string()));
+                continue;
+            }
+            syntax::Segment::Placeholder(placeholder) => placeholder,
+        };
+
+        let expr = &arg[placeholder.expr.clone()];
+        if let (true, Some(Segment::Text(text))) = (placeholder.self_doc, segments.last_mut()) {
+            text.push_str(&escape_brackets(expr.trim()));
+            text.push('=');
+        }
+
+        let spec_start = placeholder.span.start + 1;
+        segments.push(Segment::Expr(Expr {
+            spec: placeholder.spec.as_ref().map(|spec| format_spec(arg, spec, embedded, &mut counts)).unwrap_or_default(),
+            text: expr.to_string(),
+            spec_span: placeholder.spec.map_or(spec_start..spec_start, |spec| spec.span),
+            span: placeholder.expr,
+            placeholder: placeholder.span,
+        }));
+    }
+
+    (segments, counts)
+}
+
+// the spec for `format!`, with the `*` width and precision expressions added to `counts` and
+// replaced by named arguments, and `N$` counts moved past the `embedded` expressions
+fn format_spec(arg: &str, spec: &syntax::Spec, embedded: usize, counts: &mut Vec<String>) -> String {
+    let mut result = String::from(":");
+    result.extend(spec.fill);
+    result.push_str(match spec.align {
+        Some(syntax::Align::Left) => "<",
+        Some(syntax::Align::Center) => "^",
+        Some(syntax::Align::Right) => ">",
+        None => "",
+    });
+    result.push_str(match spec.sign {
+        Some(syntax::Sign::Plus) => "+",
+        Some(syntax::Sign::Minus) => "-",
+        None => "",
+    });
+    if spec.alternate {
+        result.push('#');
+    }
+    if spec.zero {
+        result.push('0');
+    }
+
+    let mut count = |count: &syntax::Count| match count {
+        syntax::Count::Literal(value) => value.to_string(),
+        syntax::Count::Positional(index) => format!("{}$", index + embedded),
+        syntax::Count::Named(name) => format!("{}$", &arg[name.clone()]),
+        syntax::Count::Expr(expr) => {
+            counts.push(span::mark(&arg[expr.clone()], expr.clone()));
+            format!("{}$", count_name(counts.len() - 1))
+        }
+    };
+
+    if let Some(width) = &spec.width {
+        result.push_str(&count(width));
+    }
+    if let Some(precision) = &spec.precision {
+        result.push('.');
+        result.push_str(&count(precision));
+    }
+    result.extend(spec.kind);
+    if spec.debug {
+        result.push('?');
+    }
+
+    result
+}
+
+// `named` are the names of the named arguments after the template, placeholders with only one of
+// these names refer to them, empty placeholders refer to the positional arguments after the
+// template or to `empty` if it's given
+fn parse_template(arg: &str, args: &[String], empty: Option<&str>) -> Result<Template, String> {
+    let named = named_args(args);
+    let (segments, counts) = parse_segments(arg, &named);
+
+    let is_embedded = |text: &str| is_embedded(text, &named);
+    // positional arguments come after the embedded expressions
+    let embedded = segments
+        .iter()
+        .filter(|segment| matches!(segment, Segment::Expr(expr) if is_embedded(expr.text.trim())))
+        .count();
+    let mut positional = embedded;
+
+    let mut fmt = String::with_capacity(arg.len());
+    let mut exprs = Vec::new();
+
+    for segment in segments {
+        let expr = match segment {
+            Segment::Text(text) => {
+                fmt.push_str(&text);
+                continue;
+            }
+            Segment::Expr(expr) => expr,
+        };
+
+        let text = expr.text.trim();
+        fmt.push('{');
+        if !text.is_empty() && !is_embedded(text) {
+            fmt.push_str(text);
+        } else if text.is_empty() {
+            match empty {
+                Some(empty) => fmt.push_str(empty),
+                None => {
+                    let spec = &arg[expr.spec_span.clone()];
+                    if positional - embedded >= args.len() - named.len() {
+                        if let Some(error) = missing_positional(spec, expr.placeholder.clone()) {
+                            return Err(error);
+                        }
+                    }
+                    fmt.push_str(&positional.to_string());
+                    positional += 1;
+                }
+            }
+        }
+        fmt.push_str(&expr.spec);
+        fmt.push('}');
+
+        if is_embedded(text) {
+            exprs.push(expr);
+        }
+    }
+
+    Ok(Template { fmt, exprs, counts })
+}
+
+// whether the placeholder text is an expression of the template rather than a named argument
+fn is_embedded(text: &str, named: &[String]) -> bool {
+    !text.is_empty() && !named.iter().any(|name| name == text)
+}
+
+// the error for a placeholder without an expression or a positional argument when its spec ends in
+// a type like `x` in `{:04x}`, which was likely meant as the expression
+fn missing_positional(spec: &str, placeholder: Range<usize>) -> Option<String> {
+    let kind = spec.chars().last().filter(|&c| "oxXpbeE".contains(c))?;
+    let flags = &spec[..spec.len() - 1];
+    let suggestion = if flags == ":" { format!("{{{}}}", kind) } else { format!("{{{} {}}}", flags, kind) };
+    let message = format!(
+        "`{{{}}}` formats a positional argument but there's none, write `{}` to format `{}`",
+        spec, suggestion, kind
+    );
+    Some(span::error_at(&message, placeholder))
+}
+
+// splits the string literal at the start of `arg` from the arguments after it
+fn split_template(arg: &str) -> (&str, Vec<String>) {
+    let mut parts = syntax::split_arguments(arg).into_iter();
+    let template = parts.next().unwrap_or_default().trim();
+    let trailing = parts
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(String::from)
+        .collect();
+
+    (template, trailing)
+}
+
+// writes to the locked `stream`, returning the `io::Result` instead of panicking, the trait isn't
+// imported so the methods called in the expressions stay the same
+fn try_print_impl(stream: &str, newline: bool, arg: &str) -> String {
+    let args = ex_impl("::std::format_args", arg);
+    let args = if newline { format!(r#"::std::format_args!("{{}}\n",{})"#, args) } else { args };
+    format!("::std::io::Write::write_fmt(&mut ::std::io::{}().lock(),{})", stream, args)
+}
+
+// dedents the string literal at the start of `arg` and keeps the arguments after it
+fn dedent_template(arg: &str) -> String {
+    let (template, trailing) = split_template(arg);
+    let mut parts = vec![dedent(template)];
+    parts.extend(trailing);
+    parts.join(",")
+}
+
+// removes the line break right after the opening quote of the string literal `arg` and the
+// common leading whitespace of its lines, ignoring lines with only whitespace and lines that
+// start inside a placeholder, which are left alone
+fn dedent(arg: &str) -> String {
+    let (body_start, body_end) = match (arg.find('"'), arg.rfind('"')) {
+        (Some(start), Some(end)) if start < end => (start + 1, end),
+        _ => return arg.to_string(),
+    };
+
+    // inside of the placeholders, without their opening bracket
+    let placeholders: Vec<_> = syntax::parse(arg)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            syntax::Segment::Placeholder(placeholder) => Some(placeholder.span.start + 1..placeholder.span.end),
+            syntax::Segment::Text(_) => None,
+        })
+        .collect();
+
+    // start of each line after the first one and the length of its leading whitespace
+    let lines: Vec<(usize, usize)> = arg[body_start..body_end]
+        .match_indices('\n')
+        .map(|(i, _)| body_start + i + 1)
+        .filter(|start| !placeholders.iter().any(|range| range.contains(start)))
+        .map(|start| {
+            let indent = arg[start..body_end]
+                .find(|c: char| c != ' ' && c != '\t')
+                .unwrap_or(body_end - start);
+            (start, indent)
+        })
+        .collect();
+
+    let common = lines
+        .iter()
+        .filter(|(start, indent)| {
+            let rest = &arg[(start + indent)..body_end];
+            !(rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n"))
+        })
+        .map(|(_, indent)| *indent)
+        .min()
+        .unwrap_or(0);
+
+    let mut result = String::with_capacity(arg.len());
+    let mut index = body_start;
+    result.push_str(&arg[..body_start]);
+    if arg[body_start..].starts_with('\n') {
+        index += 1;
+    } else if arg[body_start..].starts_with("\r\n") {
+        index += 2;
+    }
+
+    for (start, indent) in lines {
+        result.push_str(&arg[index..start]);
+        index = start + indent.min(common);
+    }
+
+    result.push_str(&arg[index..]);
+    result
+}
+
+// names of the `name = expr` arguments
+fn named_args(args: &[String]) -> Vec<String> {
+    let named = Regex::new(r#"^([A-Za-z_]\w*)\s*=([^=]|$)"#).unwrap();
+    args.iter()
+        .filter_map(|arg| named.captures(arg).map(|found| found[1].to_string()))
+        .collect()
+}
+
+// the expressions are evaluated each time the result is formatted
+fn ex_display_impl(arg: &str) -> String {
+    let write = ex_impl_with_args("::std::write", &["__ex_f".to_string()], arg, None);
+    format!("::expression_format::LazyDisplay::new(|__ex_f: &mut ::std::fmt::Formatter| {})", write)
+}
+
+// each expression is formatted with its spec and HTML escaped, unless it starts with `!`
+fn ex_html_impl(arg: &str) -> String {
+    const HTML: &str = "::expression_format::html";
+
+    let escape = |_: &str, expr: &Expr, counts: &[String]| {
+        let write = format!("::std::write!(__ex_w,{})", value_args(&expr.spec, "__ex_v", counts));
+        format!("(&&{}::Value::new(&({}), |__ex_w, __ex_v| {})).html()", HTML, expr.code(), write)
+    };
+
+    match escape_template("ex_html", arg, escape) {
+        Ok(args) => format!(
+            "{{ use {0}::{{EscapedValue as _, SafeValue as _}}; {0}::SafeHtml::from_trusted(::std::format!({1})) }}",
+            HTML, args
+        ),
+        Err(error) => error,
+    }
+}
+
+// each expression is percent-encoded for the part of the URL it's in, unless it starts with `!`
+fn ex_url_impl(arg: &str, parse: bool) -> String {
+    const PRIVATE: &str = "::expression_format::__private";
+
+    let name = if parse { "ex_url_parse" } else { "ex_url" };
+    let encode = |before: &str, expr: &Expr, counts: &[String]| {
+        let component = match url_component(before) {
+            UrlComponent::PathSegment => "PathSegment",
+            UrlComponent::QueryKey => "QueryKey",
+            UrlComponent::QueryValue => "QueryValue",
+            UrlComponent::Fragment => "Fragment",
+        };
+        let value = format_value(&expr.spec, &expr.code(), counts);
+        format!("{0}::Encode({0}::UrlComponent::{1}, {2})", PRIVATE, component, value)
+    };
+
+    match escape_template(name, arg, encode) {
+        Ok(args) if parse => format!("{}::url::Url::parse(&::std::format!({}))", PRIVATE, args),
+        Ok(args) => format!("::std::format!({})", args),
+        Err(error) => error,
+    }
+}
+
+enum UrlComponent {
+    PathSegment,
+    QueryKey,
+    QueryValue,
+    Fragment,
+}
+
+// the part of the URL that follows `before`, which starts with the opening quote of the literal
+fn url_component(before: &str) -> UrlComponent {
+    let before = before.trim_start_matches('r').trim_start_matches('#');
+    let before = before.strip_prefix('"').unwrap_or(before);
+    before.chars().fold(UrlComponent::PathSegment, |component, c| match (component, c) {
+        (UrlComponent::Fragment, _) | (_, '#') => UrlComponent::Fragment,
+        (UrlComponent::PathSegment, '?') | (_, '&') => UrlComponent::QueryKey,
+        (UrlComponent::QueryKey, '=') => UrlComponent::QueryValue,
+        (component, _) => component,
+    })
+}
+
+// returns the arguments of `format!` with the code of each expression given by `escape` from
+// the template before it, or the error if the template can't be used
+fn escape_template<F>(name: &str, arg: &str, escape: F) -> Result<String, String>
+where
+    F: Fn(&str, &Expr, &[String]) -> String,
+{
+    let (arg, trailing) = split_template(arg);
+    if !trailing.is_empty() {
+        return Err(compile_error(&format!("`{}!` doesn't take arguments after the template", name)));
+    }
+
+    let (segments, counts) = parse_segments(arg, &[]);
+    let mut ex_args = vec![String::new()];
+
+    for segment in segments {
+        let expr = match segment {
+            Segment::Text(text) => {
+                ex_args[0].push_str(&text);
+                continue;
+            }
+            Segment::Expr(expr) => expr,
+        };
+
+        if expr.text.trim().is_empty() {
+            let message = format!("`{}!` doesn't take positional arguments, write the expression inside the `{{}}`", name);
+            return Err(span::error_at(&message, expr.placeholder));
+        }
+
+        let value = match expr.text.trim_start().strip_prefix('!') {
+            Some(text) => format_value(&expr.spec, &expr.code_of(text), &counts),
+            None => escape(&ex_args[0], &expr, &counts),
+        };
+        ex_args[0].push_str("{}");
+        ex_args.push(value);
+    }
+
+    Ok(ex_args.join(","))
+}
+
+// formats the expression on its own with its spec and the counts the spec refers to
+fn format_value(spec: &str, text: &str, counts: &[String]) -> String {
+    format!("::std::format_args!({})", value_args(spec, text, counts))
+}
+
+// the format string with `spec` and the arguments formatting `text` with it
+fn value_args(spec: &str, text: &str, counts: &[String]) -> String {
+    let mut value = vec![format!(r#""{{{}}}""#, spec), text.to_string()];
+    for (i, count) in counts.iter().enumerate() {
+        if spec.contains(&format!("{}$", count_name(i))) {
+            value.push(format!("{}={}", count_name(i), count));
+        }
+    }
+
+    value.join(",")
+}
+
+// the dialect is an optional identifier before the template, like `numbered, "..."`
+fn split_sql_args(item: TokenStream) -> (Vec<String>, String) {
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let has_dialect = match &tokens[..] {
+        [TokenTree::Ident(_), TokenTree::Punct(comma), ..] => comma.as_char() == ',',
+        _ => false,
+    };
+
+    split_args(tokens.into_iter().collect(), has_dialect as usize)
+}
+
+// the expressions are replaced with placeholders and passed by reference as parameters,
+// with the `named` dialect each name is bound once
+fn ex_sql_impl(args: &[String], arg: &str) -> String {
+    let dialect = args.first().map(|dialect| dialect.trim());
+    if let Some(dialect) = dialect.filter(|&dialect| dialect != "numbered" && dialect != "named") {
+        return compile_error(&format!(
+            "unknown dialect `{}`, expected `numbered` or `named`",
+            dialect
+        ));
+    }
+
+    let (arg, trailing) = split_template(arg);
+    if !trailing.is_empty() {
+        return compile_error("`ex_sql!` doesn't take arguments after the template");
+    }
+
+    let (segments, _) = parse_segments(arg, &[]);
+    let mut sql = String::new();
+    let mut names: Vec<(String, String)> = Vec::new();
+    let mut params = Vec::new();
+
+    for segment in segments {
+        let expr = match segment {
+            Segment::Text(text) => {
+                sql.push_str(&text.replace("{{", "{").replace("}}", "}"));
+                continue;
+            }
+            Segment::Expr(expr) => expr,
+        };
+
+        if !expr.spec.is_empty() {
+            let message = format!("`ex_sql!` doesn't take format specs, remove `{}` from `{}`", expr.spec, expr.text.trim());
+            return span::error_at(&message, expr.spec_span);
+        }
+
+        match dialect {
+            Some("named") => {
+                let (name, value) = match field_name(&expr.text) {
+                    Some((name, value)) => (name.replace('.', "_"), value.trim().to_string()),
+                    None => {
+                        let message = format!("cannot name the parameter for `{}`, use `{{name = expr}}` instead", expr.text.trim());
+                        return span::error_at(&message, expr.span);
+                    }
+                };
+
+                sql.push(':');
+                sql.push_str(&name);
+                match names.iter().find(|(bound, _)| *bound == name) {
+                    Some((_, bound)) if *bound != value => {
+                        let message = format!("parameter `:{}` is bound to `{}` and `{}`", name, bound, value);
+                        return span::error_at(&message, expr.span);
+                    }
+                    Some(_) => continue,
+                    None => names.push((name, value.clone())),
+                }
+                params.push(expr.code_of(&value));
+            }
+            Some(_) => {
+                sql.push_str(&format!("${}", params.len() + 1));
+                params.push(expr.code());
+            }
+            None => {
+                sql.push('?');
+                params.push(expr.code());
+            }
+        }
+    }
+
+    let params: String = params.iter().map(|param| format!("&({}),", param)).collect();
+    format!("::expression_format::sql::Query {{ sql: {}, params: ({}) }}", sql, params)
+}
+
+// part of a command line argument
+enum CmdPart {
+    Text(String),
+    Value(String),
+    // the expression after `..` and the placeholder it's in
+    Spread(String, Expr),
+}
+
+// the literal parts are split on whitespace, an expression is always part of a single argument
+// unless it's spread with `{..expr}`
+fn ex_cmd_impl(arg: &str) -> String {
+    const PRIVATE: &str = "::expression_format::__private";
+
+    let (arg, trailing) = split_template(arg);
+    if !is_string_literal(arg) {
+        return compile_error("`ex_cmd!` expects a command template");
+    }
+    if !trailing.is_empty() {
+        return compile_error("`ex_cmd!` doesn't take arguments after the template");
+    }
+
+    let (segments, counts) = parse_segments(arg, &[]);
+    let last = segments.len() - 1;
+    let mut words: Vec<Vec<CmdPart>> = Vec::new();
+    let mut word = Vec::new();
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        let expr = match segment {
+            Segment::Text(text) => {
+                for c in text_value(arg, &text, i == 0, i == last).chars() {
+                    match word.last_mut() {
+                        _ if c.is_whitespace() => {
+                            if !word.is_empty() {
+                                words.push(std::mem::take(&mut word));
+                            }
+                        }
+                        Some(CmdPart::Text(text)) => text.push(c),
+                        _ => word.push(CmdPart::Text(c.to_string())),
+                    }
+                }
+                continue;
+            }
+            Segment::Expr(expr) => expr,
+        };
+
+        if let Some(spread) = expr.text.trim_start().strip_prefix("..") {
+            if !expr.spec.is_empty() {
+                return span::error_at(&format!("`{{..{}}}` can't have a format spec", spread.trim()), expr.spec_span);
+            }
+            word.push(CmdPart::Spread(spread.trim().to_string(), expr));
+        } else if expr.spec.is_empty() {
+            word.push(CmdPart::Value(expr.code()));
+        } else {
+            word.push(CmdPart::Value(format_value(&expr.spec, &expr.code(), &counts)));
+        }
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    let mut code = vec![format!("use {}::{{Arg, ToArg as _, ToDisplayArg as _}};", PRIVATE)];
+    for (i, word) in words.iter().enumerate() {
+        let arg = match &word[..] {
+            [CmdPart::Text(text)] => format!("{:?}", text),
+            [CmdPart::Value(value)] => format!("(&Arg(&({}))).to_arg()", value),
+            [CmdPart::Spread(spread, expr)] if i > 0 => {
+                code.push(format!(
+                    "for __ex_item in {} {{ __ex_cmd.arg((&Arg(&__ex_item)).to_arg()); }}",
+                    expr.code_of(spread)
+                ));
+                continue;
+            }
+            [CmdPart::Spread(spread, expr)] => {
+                let message = format!("the program can't be spread, remove `..` from `{{..{}}}`", spread);
+                return span::error_at(&message, expr.placeholder.clone());
+            }
+            parts => {
+                let mut pushes = vec!["let mut __ex_arg = ::std::ffi::OsString::new();".to_string()];
+                for part in parts {
+                    match part {
+                        CmdPart::Text(text) => pushes.push(format!("__ex_arg.push({:?});", text)),
+                        CmdPart::Value(value) => pushes.push(format!("__ex_arg.push((&Arg(&({}))).to_arg());", value)),
+                        CmdPart::Spread(spread, expr) => {
+                            let message = format!("`{{..{}}}` must be a separate argument", spread);
+                            return span::error_at(&message, expr.placeholder.clone());
+                        }
+                    }
+                }
+                format!("{{ {} __ex_arg }}", pushes.concat())
+            }
+        };
+
+        if i == 0 {
+            code.push(format!("let mut __ex_cmd = ::std::process::Command::new({});", arg));
+        } else {
+            code.push(format!("__ex_cmd.arg({});", arg));
+        }
+    }
+
+    if words.is_empty() {
+        return compile_error("`ex_cmd!` needs a program");
+    }
+
+    format!("{{ {} __ex_cmd }}", code.concat())
+}
+
+// the text between the expressions must match the input, and the text for each expression
+// ends where the next text starts or after the width in its spec, the places are only assigned
+// if the whole input matches
+fn ex_scan_impl(args: &[String], arg: &str) -> String {
+    const SCAN: &str = "::expression_format::scan";
+
+    let input = match args.first() {
+        Some(input) => input,
+        None => return compile_error("`ex_scan!` needs an input and a template"),
+    };
+
+    let (arg, trailing) = split_template(arg);
+    if !is_string_literal(arg) {
+        return compile_error("`ex_scan!` needs an input and a template");
+    }
+    if !trailing.is_empty() {
+        return compile_error("`ex_scan!` doesn't take arguments after the template");
+    }
+
+    let (segments, _) = parse_segments(arg, &[]);
+    let last = segments.len() - 1;
+    let mut texts = Vec::new();
+    let mut exprs = Vec::new();
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        match segment {
+            Segment::Text(text) => texts.push(text_value(arg, &text, i == 0, i == last)),
+            Segment::Expr(expr) => exprs.push(expr),
+        }
+    }
+
+    let spec_regex = Regex::new(r#"^(?::[<\^>]?(#)?0?(\d+)?([xXob])?)?$"#).unwrap();
+    let mut code = vec![format!("let mut __ex_scanner = {}::Scanner::new(__ex_input);", SCAN)];
+    let mut places = Vec::new();
+
+    for (i, expr) in exprs.iter().enumerate() {
+        let placeholder = &arg[expr.placeholder.clone()];
+        if !texts[i].is_empty() {
+            code.push(format!("__ex_scanner.literal({:?})?;", texts[i]));
+        }
+
+        let spec = match spec_regex.captures(&expr.spec) {
+            Some(spec) => spec,
+            None => return span::error_at(&format!("unsupported format spec in `{}`", placeholder), expr.spec_span.clone()),
+        };
+
+        let capture = match spec.get(2) {
+            Some(width) => format!("__ex_scanner.width({:?}, {})?", placeholder, width.as_str()),
+            None if texts[i + 1].is_empty() && i + 1 < exprs.len() => {
+                return span::error_at(&format!("`{}` needs a width or text after it", placeholder), expr.placeholder.clone());
+            }
+            None => format!("__ex_scanner.until({:?}, {:?})?", placeholder, texts[i + 1]),
+        };
+
+        if expr.text.trim().is_empty() {
+            code.push(format!("{};", capture));
+            continue;
+        }
+
+        let (radix, prefix) = match spec.get(3).map(|kind| kind.as_str()) {
+            Some("x") => (16, "0x"),
+            Some("X") => (16, "0X"),
+            Some("o") => (8, "0o"),
+            Some("b") => (2, "0b"),
+            _ => (10, ""),
+        };
+        let parse = match (radix, spec.get(1)) {
+            (10, _) => "parse()".to_string(),
+            (_, Some(_)) => format!("parse_radix({}, {:?})", radix, prefix),
+            (_, None) => format!("parse_radix({}, \"\")", radix),
+        };
+
+        code.push(format!("let __ex_value{} = {}.{}?;", places.len(), capture, parse));
+        places.push(expr.code());
+    }
+
+    if !texts[exprs.len()].is_empty() {
+        code.push(format!("__ex_scanner.literal({:?})?;", texts[exprs.len()]));
+    }
+    code.push("__ex_scanner.finish()?;".to_string());
+
+    let values: String = (0..places.len()).map(|i| format!("__ex_value{},", i)).collect();
+    let assignments: String =
+        places.iter().enumerate().map(|(i, place)| format!("{} = __ex_value{};", place, i)).collect();
+
+    format!(
+        concat!(
+            "{{ let __ex_input = &({input});",
+            "match (|| -> ::std::result::Result<_, {scan}::ScanError> {{",
+            "let __ex_input = ::std::convert::AsRef::<str>::as_ref(__ex_input);",
+            "{code} ::std::result::Result::Ok(({values})) }})() {{",
+            "::std::result::Result::Ok(({values})) => {{ {assignments} ::std::result::Result::Ok(()) }}",
+            "::std::result::Result::Err(__ex_error) => ::std::result::Result::Err(__ex_error), }} }}",
+        ),
+        input = input,
+        scan = SCAN,
+        code = code.concat(),
+        values = values,
+        assignments = assignments,
+    )
+}
+
+// whether `arg` is a string literal, which `text_value` expects
+fn is_string_literal(arg: &str) -> bool {
+    let quoted = arg.starts_with('"') || arg.starts_with("r\"") || arg.starts_with("r#");
+    quoted && arg.len() >= 2 && (arg.ends_with('"') || arg.ends_with('#'))
+}
+
+// the value of a text segment of the literal `arg`, without the quotes, escape sequences or
+// escaped brackets
+fn text_value(arg: &str, text: &str, first: bool, last: bool) -> String {
+    let mut text = text;
+    if last {
+        text = &text[..text.rfind('"').unwrap()];
+    }
+    if first {
+        text = &text[text.find('"').unwrap() + 1..];
+    }
+
+    let text = if arg.starts_with('r') { text.to_string() } else { unescape(text) };
+    text.replace("{{", "{").replace("}}", "}")
+}
+
+// resolves the escape sequences of a valid string literal
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next().unwrap() {
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            '0' => result.push('\0'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                result.push(u8::from_str_radix(&hex, 16).unwrap() as char);
+            }
+            'u' if chars.peek() == Some(&'{') => {
+                let hex: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                let code = u32::from_str_radix(&hex.replace('_', ""), 16).unwrap();
+                result.push(std::char::from_u32(code).unwrap());
+            }
+            '\n' => {
+                while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+// passes the formatted message as a single `{}` argument after `args` so that `func` doesn't
+// depend on the edition rules for a lone string literal, the message is optional
+fn ex_message_impl(func: &str, args: &[String], arg: &str) -> String {
+    if arg.trim().is_empty() {
+        return format!("{}!({})", func, args.join(","));
+    }
+
+    let mut args = args.to_vec();
+    args.push(r#""{}""#.to_string());
+
+    let message = ex_impl("::std::format_args", arg);
+    format!("{}!({},{})", func, args.join(","), message)
+}
+
+// `count` is the number of arguments before the optional message
+fn ex_assert_impl(func: &str, item: TokenStream, count: usize) -> TokenStream {
+    span::expand(item, count, |item| {
+        let (mut args, mut arg) = split_args(item, count);
+        if args.len() < count {
+            args.push(std::mem::take(&mut arg));
+        }
+
+        ex_message_impl(func, &args, &arg)
+    })
+}
+
+fn ex_log_impl(func: &str, item: TokenStream, count: usize) -> TokenStream {
+    span::expand(item, count, |item| {
+        let (args, arg) = split_log_args(item, count);
+        let func = format!("::expression_format::__private::log::{}", func);
+        ex_impl_with_args(&func, &args, &arg, None)
+    })
+}
+
+// `count` is the number of arguments before the template, not counting an optional leading
+// `target: expr`
+fn split_log_args(item: TokenStream, count: usize) -> (Vec<String>, String) {
+    let tokens: Vec<TokenTree> = item.into_iter().collect();
+    let has_target = match &tokens[..] {
+        [TokenTree::Ident(ident), TokenTree::Punct(colon), ..] => {
+            ident.to_string() == "target" && colon.as_char() == ':' && colon.spacing() == Spacing::Alone
+        }
+        _ => false,
+    };
+
+    let count = if has_target { count + 1 } else { count };
+    split_args(tokens.into_iter().collect(), count)
+}
+
+// the expressions are evaluated once, only if the event is enabled, and each of them is also
+// recorded as a field named after the expression or an explicit `{name = expr}`
+fn ex_event_impl(args: &[String], arg: &str) -> String {
+    const TRACING: &str = "::expression_format::__private::tracing";
+
+    let (arg, trailing) = split_template(arg);
+    let template = match parse_template(arg, &trailing, None) {
+        Ok(template) => template,
+        Err(error) => return error,
+    };
+    let mut names = Vec::new();
+    let mut fields = Vec::new();
+    let mut values = Vec::new();
+    let mut bindings = Vec::new();
+
+    for (i, expr) in template.exprs.iter().enumerate() {
+        let (name, value) = match field_name(&expr.text) {
+            Some(field) => field,
+            None => {
+                let message = format!("cannot name the field for `{}`, use `{{name = expr}}` instead", expr.text.trim());
+                return span::error_at(&message, expr.span.clone());
+            }
+        };
+
+        let binding = format!("__ex_field{}", i);
+        if !names.contains(&name) {
+            let sigil = if expr.spec.contains('?') { '?' } else { '%' };
+            fields.push(format!("{} = {}{}", name, sigil, binding));
+            names.push(name);
+        }
+        values.push(format!("&({})", expr.code_of(&value)));
+        bindings.push(binding);
+    }
+
+    let mut message = vec![template.fmt];
+    message.extend(bindings.iter().cloned());
+    message.extend(trailing);
+    for (i, count) in template.counts.into_iter().enumerate() {
+        message.push(format!("{}={}", count_name(i), count));
+    }
+
+    let mut event_args = args.to_vec();
+    event_args.extend(fields);
+    event_args.extend(message);
+
+    format!(
+        "if {}::enabled!({}) {{ match ({},) {{ ({},) => {}::event!({}), }} }}",
+        TRACING,
+        args.join(","),
+        values.join(","),
+        bindings.join(","),
+        TRACING,
+        event_args.join(",")
+    )
+}
+
+// returns the field name and the expression of `{name = expr}` or of a path like `{user.id}`
+fn field_name(text: &str) -> Option<(String, String)> {
+    let path = Regex::new(r#"^\s*([A-Za-z_]\w*(?:\s*\.\s*[A-Za-z_]\w*)*)\s*"#).unwrap();
+    let found = path.captures(text)?;
+    let name: String = found[1].split_whitespace().collect();
+    let rest = &text[found[0].len()..];
+
+    if rest.is_empty() {
+        let name = name.strip_prefix("self.").map(String::from).unwrap_or(name);
+        Some((name, text.to_string()))
+    } else if rest.starts_with('=') && !rest.starts_with("==") {
+        Some((name, rest[1..].to_string()))
+    } else {
+        None
+    }
+}
+
+// the message is only formatted if `args` holds an error
+fn ex_with_context_impl(args: &[String], arg: &str) -> String {
+    let result = match args.first() {
+        Some(result) => result,
+        None => return compile_error("`ex_with_context!` needs a result and a template"),
+    };
+
+    format!(
+        "::expression_format::__private::anyhow::Context::with_context({}, || {})",
+        result,
+        ex_impl("::std::format", arg)
+    )
+}
+
+// `args` holds the template, empty placeholders in it refer to `value` which is returned
+fn ex_dbg_impl(args: &[String], value: &str) -> String {
+    const VALUE: &str = "__ex_dbg_value";
+
+    let (template, value) = match args.first() {
+        Some(template) => (template.as_str(), value.trim()),
+        None => (value, ""),
+    };
+
+    let message = ex_impl_with_args("::std::format_args", &[], template, Some(VALUE));
+    let print = format!(
+        r#"::std::eprintln!("[{{}}:{{}}] {{}}", ::std::file!(), ::std::line!(), {})"#,
+        message
+    );
+
+    if value.is_empty() {
+        print
+    } else {
+        format!("match {} {{ {} => {{ {}; {} }} }}", value, VALUE, print, VALUE)
+    }
+}
+
+fn compile_error(message: &str) -> String {
+    format!("::std::compile_error!({:?})", message)
+}
+
+fn escape_brackets(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+fn count_name(index: usize) -> String {
+    format!("__ex_count{}", index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unbalanced_bracket_in_string_with_escape() {
+        test_helper(r#""lorem {"{\"{"} ipsum""#, r#""lorem {} ipsum","{\"{""#);
+    }
+
+    #[test]
+    fn test_unbalanced_bracket_in_char() {
+        test_helper(r#""lorem {'{'} ipsum""#, r#""lorem {} ipsum",'{'"#);
+    }
+
+    #[test]
+    fn test_unbalanced_bracket_in_string() {
+        test_helper(
+            r#""lorem {"{dolor"} ipsum""#,
+            r#""lorem {} ipsum","{dolor""#,
+        );
+    }
+
+    #[test]
+    fn test_struct_create() {
+        test_helper(
+            r#""lorem {Dolor{sit:"amet"}} ipsum""#,
+            r#""lorem {} ipsum",Dolor{sit:"amet"}"#,
+        );
+    }
+
+    #[test]
+    fn test_literal_string() {
+        test_helper(
+            r#""lorem {"dolor sit amet"} ipsum""#,
+            r#""lorem {} ipsum","dolor sit amet""#,
+        );
+    }
+
+    #[test]
+    fn test_consecutive_formats() {
+        test_helper(r#""{lorem}{ipsum}""#, r#""{}{}",lorem,ipsum"#);
+    }
+
+    #[test]
+    fn test_escaped_open_brackets() {
+        test_helper(r#""lorem {{ ipsum""#, r#""lorem {{ ipsum""#);
+    }
+
+    #[test]
+    fn test_escaped_close_brackets() {
+        test_helper(r#""lorem }} ipsum""#, r#""lorem }} ipsum""#);
+    }
+
+    #[test]
+    fn test_escaped_brackets() {
+        test_helper(r#""{{}}""#, r#""{{}}""#);
+    }
+
+    #[test]
+    fn test_no_format() {
+        test_helper(r#""lorem ipsum""#, r#""lorem ipsum""#);
+    }
+
+    #[test]
+    fn test_no_arg() {
+        test_helper("", "");
+    }
+
+    #[test]
+    fn test_empty_str_arg() {
+        test_helper(r#""""#, r#""""#);
+    }
+
+    #[test]
+    fn test_empty_marker() {
+        test_helper(r#""lorem {} ipsum""#, r#""lorem {0} ipsum""#);
+    }
+
+    #[test]
+    fn test_literal_number() {
+        test_helper(r#""lorem {123} ipsum""#, r#""lorem {} ipsum",123"#);
+    }
+
+    #[test]
+    fn test_one_argument() {
+        test_helper(r#""lorem {dolor} ipsum""#, r#""lorem {} ipsum",dolor"#);
+    }
+
+    #[test]
+    fn test_multiple_same_argument() {
+        test_helper(
+            r#""lorem {dolor} ipsum {dolor} sit {dolor} amet""#,
+            r#""lorem {} ipsum {} sit {} amet",dolor,dolor,dolor"#,
+        );
+    }
+
+    #[test]
+    fn test_multiple_arguments() {
+        test_helper(
+            r#""lorem {ipsum} dolor {sit} amet, {consectetur} adipiscing""#,
+            r#""lorem {} dolor {} amet, {} adipiscing",ipsum,sit,consectetur"#,
+        );
+    }
+
+    #[test]
+    fn test_nested_argument() {
+        test_helper(
+            r#""lorem {dolor.sit} ipsum""#,
+            r#""lorem {} ipsum",dolor.sit"#,
+        );
+    }
+
+    #[test]
+    fn test_array_argument() {
+        test_helper(
+            r#""lorem {dolor[0]} ipsum""#,
+            r#""lorem {} ipsum",dolor[0]"#,
+        );
+    }
+
+    #[test]
+    fn test_function_argument() {
+        test_helper(
+            r#""lorem {dolor(arg)} ipsum""#,
+            r#""lorem {} ipsum",dolor(arg)"#,
+        );
+    }
+
+    #[test]
+    fn test_debug_argument() {
+        test_helper(r#""lorem {:?dolor} ipsum""#, r#""lorem {:?} ipsum",dolor"#);
+    }
+
+    #[test]
+    fn test_mixed_empty_argument() {
+        test_helper(
+            r#""lorem {dolor} ipsum {} sit""#,
+            r#""lorem {} ipsum {1} sit",dolor"#,
+        );
+    }
+
+    #[test]
+    fn test_quote_outside_expression() {
+        test_helper(r#""lorem "{"ipsum"}"""#, r#""lorem "{}"","ipsum""#);
+    }
+
+    #[test]
+    fn test_raw_string() {
+        test_helper(
+            r###""{r##""{lorem}"#"{""##} {ipsum}""###,
+            r###""{} {}",r##""{lorem}"#"{""##,ipsum"###,
+        );
+    }
+
+    #[test]
+    fn test_line_comment() {
+        test_helper(
+            r#""{ {// lorem ipsum { "
+"dolor"} }""#,
+            r#""{}", {// lorem ipsum { "
+"dolor"} "#,
+        );
+    }
+
+    #[test]
+    fn test_block_comment() {
+        test_helper(r#""{/*lorem { ipsum*/10}""#, r#""{}",/*lorem { ipsum*/10"#);
+    }
+
+    #[test]
+    fn test_nested_block_comment() {
+        test_helper(
+            r#""lorem {/*/*inside comment*/still inside comment*/"ipsum"}""#,
+            r#""lorem {}",/*/*inside comment*/still inside comment*/"ipsum""#,
+        );
+    }
+
+    #[test]
+    fn test_format_width() {
+        test_helper(r#""{:04 42}""#, r#""{:04}", 42"#);
+    }
+
+    #[test]
+    fn test_format_alignment_with_char() {
+        test_helper(r#""{:'>10 "test"}""#, r#""{:'>10}", "test""#);
+    }
+
+    #[test]
+    fn test_format_width_argument() {
+        test_helper(r#""{:>w$ lorem}""#, r#""{:>w$}", lorem"#);
+    }
+
+    #[test]
+    fn test_format_width_index() {
+        test_helper(r#""{:1$ lorem}""#, r#""{:2$}", lorem"#);
+    }
+
+    #[test]
+    fn test_format_precision_argument() {
+        test_helper(r#""{:.p$ lorem}""#, r#""{:.p$}", lorem"#);
+    }
+
+    #[test]
+    fn test_format_width_expression() {
+        test_helper(
+            r#""{:>*dolor.len lorem}""#,
+            r#""{:>__ex_count0$}", lorem,__ex_count0=dolor.len"#,
+        );
+    }
+
+    #[test]
+    fn test_format_precision_block_expression() {
+        test_helper(
+            r#""{:.*{dolor + 1} lorem} {:*w.*p ipsum}""#,
+            r#""{:.__ex_count0$} {:__ex_count1$.__ex_count2$}", lorem, ipsum,__ex_count0=dolor + 1,__ex_count1=w,__ex_count2=p"#,
+        );
+    }
+
+    #[test]
+    fn test_self_documenting() {
+        test_helper(r#""lorem {=dolor.sit} ipsum""#, r#""lorem dolor.sit={} ipsum",dolor.sit"#);
+    }
+
+    #[test]
+    fn test_self_documenting_with_spec() {
+        test_helper(
+            r#""{:?= dolor[0] } {:>5=ipsum}""#,
+            r#""dolor[0]={:?} ipsum={:>5}", dolor[0] ,ipsum"#,
+        );
+    }
+
+    #[test]
+    fn test_self_documenting_with_brackets() {
+        test_helper(
+            r#""{=Lorem { ipsum: 1 }}""#,
+            r#""Lorem {{ ipsum: 1 }}={}",Lorem { ipsum: 1 }"#,
+        );
+    }
+
+    #[test]
+    fn test_positional_arguments() {
+        test_helper(
+            r#""{lorem} {} {:?ipsum} {:>5}", 1 + 2, [3, 4].len()"#,
+            r#""{} {2} {:?} {3:>5}",lorem,ipsum,1 + 2,[3, 4].len()"#,
+        );
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        test_helper(
+            r#""{lorem} {:?ipsum} {lorem.len()}", lorem = dolor(1, 2), ipsum = sit == amet"#,
+            r#""{lorem} {ipsum:?} {}",lorem.len(),lorem = dolor(1, 2),ipsum = sit == amet"#,
+        );
+    }
+
+    #[test]
+    fn test_trailing_arguments_with_strings_and_chars() {
+        test_helper(
+            r#""{} {} {}", ",", ',', r"),""#,
+            r#""{0} {1} {2}",",",',',r"),""#,
+        );
+    }
+
+    #[test]
+    fn test_trailing_comma() {
+        test_helper(r#""{lorem} {}", ipsum,"#, r#""{} {1}",lorem,ipsum"#);
+    }
+
+    #[test]
+    fn test_dedent() {
+        assert_eq!(
+            dedent("\"\n    lorem\n      ipsum\n\n    dolor\n    \""),
+            "\"lorem\n  ipsum\n\ndolor\n\""
+        );
+    }
+
+    #[test]
+    fn test_dedent_first_line() {
+        assert_eq!(dedent("r#\"lorem\n    ipsum\n      dolor\"#"), "r#\"lorem\nipsum\n  dolor\"#");
+    }
+
+    #[test]
+    fn test_dedent_ignores_placeholder_lines() {
+        assert_eq!(
+            dedent("\"\n    lorem {{\n        ipsum\n    }} {\n  dolor\n}\n    sit\""),
+            "\"lorem {{\n    ipsum\n}} {\n  dolor\n}\nsit\""
+        );
+    }
+
+    #[test]
+    fn test_dedent_template() {
+        assert_eq!(
+            dedent_template("\"\n  {lorem}\n  {}\", ipsum , dolor = 1"),
+            "\"{lorem}\n{}\",ipsum,dolor = 1"
+        );
+    }
+
+    #[test]
+    fn test_leading_arguments() {
+        assert_eq!(
+            ex_impl_with_args("write", &["dolor".to_string()], r#""lorem {ipsum}""#, None),
+            r#"write!(dolor,"lorem {}",ipsum)"#
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            ex_display_impl(r#""lorem {ipsum}""#),
+            r#"::expression_format::LazyDisplay::new(|__ex_f: &mut ::std::fmt::Formatter| ::std::write!(__ex_f,"lorem {}",ipsum))"#
+        );
+    }
+
+    #[test]
+    fn test_event() {
+        assert_eq!(
+            ex_event_impl(&["Level::INFO".to_string()], r#""lorem {dolor.sit} {:?amet}""#),
+            r#"if ::expression_format::__private::tracing::enabled!(Level::INFO) { match (&(dolor.sit),&(amet),) { (__ex_field0,__ex_field1,) => ::expression_format::__private::tracing::event!(Level::INFO,dolor.sit = %__ex_field0,amet = ?__ex_field1,"lorem {} {:?}",__ex_field0,__ex_field1), } }"#
+        );
+    }
+
+    #[test]
+    fn test_field_name() {
+        assert_eq!(field_name(" lorem "), Some(("lorem".to_string(), " lorem ".to_string())));
+        assert_eq!(field_name("self.lorem.ipsum"), Some(("lorem.ipsum".to_string(), "self.lorem.ipsum".to_string())));
+        assert_eq!(field_name("lorem = ipsum + 1"), Some(("lorem".to_string(), " ipsum + 1".to_string())));
+        assert_eq!(field_name("lorem == ipsum"), None);
+        assert_eq!(field_name("lorem[0]"), None);
+        assert_eq!(field_name("lorem()"), None);
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            ex_html_impl(r#""<p>{lorem}</p>{{{:>5 !ipsum}}}""#),
+            concat!(
+                "{ use ::expression_format::html::{EscapedValue as _, SafeValue as _}; ",
+                r#"::expression_format::html::SafeHtml::from_trusted(::std::format!("<p>{}</p>{{{}}}","#,
+                r#"(&&::expression_format::html::Value::new(&(lorem), |__ex_w, __ex_v| ::std::write!(__ex_w,"{}",__ex_v))).html(),"#,
+                r#"::std::format_args!("{:>5}",ipsum))) }"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_html_width_expression() {
+        assert_eq!(
+            ex_html_impl(r#""{:*dolor lorem}""#),
+            concat!(
+                "{ use ::expression_format::html::{EscapedValue as _, SafeValue as _}; ",
+                r#"::expression_format::html::SafeHtml::from_trusted(::std::format!("{}","#,
+                r#"(&&::expression_format::html::Value::new(&( lorem), |__ex_w, __ex_v| ::std::write!(__ex_w,"{:__ex_count0$}",__ex_v,__ex_count0=dolor))).html())) }"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_html_positional() {
+        assert_eq!(
+            ex_html_impl(r#""<p>{}</p>""#),
+            r#"::std::compile_error!("`ex_html!` doesn't take positional arguments, write the expression inside the `{}`")"#
+        );
+    }
+
+    #[test]
+    fn test_html_arguments() {
+        assert_eq!(
+            ex_html_impl(r#""{}", lorem"#),
+            r#"::std::compile_error!("`ex_html!` doesn't take arguments after the template")"#
+        );
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            ex_url_impl(r#""{!lorem}/{ipsum}?{dolor}={sit}&a={amet}#{:?consectetur}""#, false),
+            concat!(
+                r#"::std::format!("{}/{}?{}={}&a={}#{}",::std::format_args!("{}",lorem),"#,
+                r#"::expression_format::__private::Encode(::expression_format::__private::UrlComponent::PathSegment, ::std::format_args!("{}",ipsum)),"#,
+                r#"::expression_format::__private::Encode(::expression_format::__private::UrlComponent::QueryKey, ::std::format_args!("{}",dolor)),"#,
+                r#"::expression_format::__private::Encode(::expression_format::__private::UrlComponent::QueryValue, ::std::format_args!("{}",sit)),"#,
+                r#"::expression_format::__private::Encode(::expression_format::__private::UrlComponent::QueryValue, ::std::format_args!("{}",amet)),"#,
+                r#"::expression_format::__private::Encode(::expression_format::__private::UrlComponent::Fragment, ::std::format_args!("{:?}",consectetur)))"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_url_parse() {
+        assert_eq!(
+            ex_url_impl(r#""lorem/{ipsum}""#, true),
+            concat!(
+                r#"::expression_format::__private::url::Url::parse(&::std::format!("lorem/{}","#,
+                r#"::expression_format::__private::Encode(::expression_format::__private::UrlComponent::PathSegment, ::std::format_args!("{}",ipsum))))"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_scan() {
+        assert_eq!(
+            ex_scan_impl(&["lorem".to_string()], r#""a{ipsum}-{:#x dolor.sit} {:3}{:>2 amet[0]}""#),
+            concat!(
+                "{ let __ex_input = &(lorem);",
+                "match (|| -> ::std::result::Result<_, ::expression_format::scan::ScanError> {",
+                "let __ex_input = ::std::convert::AsRef::<str>::as_ref(__ex_input);",
+                "let mut __ex_scanner = ::expression_format::scan::Scanner::new(__ex_input);",
+                r#"__ex_scanner.literal("a")?;"#,
+                r#"let __ex_value0 = __ex_scanner.until("{ipsum}", "-")?.parse()?;"#,
+                r#"__ex_scanner.literal("-")?;"#,
+                r#"let __ex_value1 = __ex_scanner.until("{:#x dolor.sit}", " ")?.parse_radix(16, "0x")?;"#,
+                r#"__ex_scanner.literal(" ")?;"#,
+                r#"__ex_scanner.width("{:3}", 3)?;"#,
+                r#"let __ex_value2 = __ex_scanner.width("{:>2 amet[0]}", 2)?.parse()?;"#,
+                "__ex_scanner.finish()?; ::std::result::Result::Ok((__ex_value0,__ex_value1,__ex_value2,)) })() {",
+                "::std::result::Result::Ok((__ex_value0,__ex_value1,__ex_value2,)) => ",
+                "{ ipsum = __ex_value0; dolor.sit = __ex_value1; amet[0] = __ex_value2; ::std::result::Result::Ok(()) }",
+                "::std::result::Result::Err(__ex_error) => ::std::result::Result::Err(__ex_error), } }",
+            )
+        );
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        assert_eq!(
+            ex_scan_impl(&["lorem".to_string()], r#""{ipsum}{dolor}""#),
+            r#"::std::compile_error!("`{ipsum}` needs a width or text after it")"#
+        );
+        assert_eq!(
+            ex_scan_impl(&["lorem".to_string()], r#""{:.2 ipsum}""#),
+            r#"::std::compile_error!("unsupported format spec in `{:.2 ipsum}`")"#
+        );
+        assert_eq!(
+            ex_scan_impl(&["lorem".to_string()], ""),
+            r#"::std::compile_error!("`ex_scan!` needs an input and a template")"#
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_missing_template() {
+        let input = syn::parse_str(r#"enum Lorem { #[ex_display("ipsum")] Ipsum, Dolor }"#).unwrap();
+        let error = derive::ex_display(&input).unwrap_err();
+        assert_eq!(error.to_string(), r#"missing `#[ex_display("...")]` attribute"#);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_error_from() {
+        let input = syn::parse_str("enum Lorem { #[ex_error(\"ipsum\")] Ipsum(#[from] Dolor, u8) }").unwrap();
+        let error = derive::ex_error(&input).unwrap_err();
+        assert_eq!(error.to_string(), "`#[from]` must be the only field");
+    }
+
+    #[test]
+    fn test_with_context() {
+        assert_eq!(
+            ex_with_context_impl(&["lorem()".to_string()], r#""ipsum {:?dolor}""#),
+            r#"::expression_format::__private::anyhow::Context::with_context(lorem(), || ::std::format!("ipsum {:?}",dolor))"#
+        );
+    }
+
+    #[test]
+    fn test_try_print() {
+        assert_eq!(
+            try_print_impl("stderr", true, r#""lorem {ipsum}""#),
+            r#"::std::io::Write::write_fmt(&mut ::std::io::stderr().lock(),::std::format_args!("{}\n",::std::format_args!("lorem {}",ipsum)))"#
+        );
+    }
+
+    #[test]
+    fn test_sql() {
+        assert_eq!(
+            ex_sql_impl(&[], r#""SELECT * FROM t WHERE a = {lorem} AND b = {ipsum.dolor()} AND c = '{{}}'""#),
+            r#"::expression_format::sql::Query { sql: "SELECT * FROM t WHERE a = ? AND b = ? AND c = '{}'", params: (&(lorem),&(ipsum.dolor()),) }"#
+        );
+    }
+
+    #[test]
+    fn test_sql_numbered() {
+        assert_eq!(
+            ex_sql_impl(&["numbered".to_string()], r#""a = {lorem} OR a = {lorem}""#),
+            r#"::expression_format::sql::Query { sql: "a = $1 OR a = $2", params: (&(lorem),&(lorem),) }"#
+        );
+    }
+
+    #[test]
+    fn test_sql_named() {
+        assert_eq!(
+            ex_sql_impl(&["named".to_string()], r#""{lorem.ipsum} {dolor = 1 + 2} {lorem.ipsum}""#),
+            r#"::expression_format::sql::Query { sql: ":lorem_ipsum :dolor :lorem_ipsum", params: (&(lorem.ipsum),&(1 + 2),) }"#
+        );
+    }
+
+    #[test]
+    fn test_sql_errors() {
+        assert_eq!(
+            ex_sql_impl(&[], r#""{:? lorem}""#),
+            r#"::std::compile_error!("`ex_sql!` doesn't take format specs, remove `:?` from `lorem`")"#
+        );
+        assert_eq!(
+            ex_sql_impl(&["named".to_string()], r#""{lorem} {lorem = 1}""#),
+            r#"::std::compile_error!("parameter `:lorem` is bound to `lorem` and `1`")"#
+        );
+        assert_eq!(
+            ex_sql_impl(&["ordered".to_string()], r#""""#),
+            r#"::std::compile_error!("unknown dialect `ordered`, expected `numbered` or `named`")"#
+        );
+    }
+
+    #[test]
+    fn test_cmd() {
+        assert_eq!(
+            ex_cmd_impl(r#""lorem -n {ipsum} --x={:>3 dolor} {..sit}""#),
+            concat!(
+                "{ use ::expression_format::__private::{Arg, ToArg as _, ToDisplayArg as _};",
+                r#"let mut __ex_cmd = ::std::process::Command::new("lorem");"#,
+                r#"__ex_cmd.arg("-n");"#,
+                "__ex_cmd.arg((&Arg(&(ipsum))).to_arg());",
+                r#"__ex_cmd.arg({ let mut __ex_arg = ::std::ffi::OsString::new();__ex_arg.push("--x=");"#,
+                r#"__ex_arg.push((&Arg(&(::std::format_args!("{:>3}", dolor)))).to_arg()); __ex_arg });"#,
+                "for __ex_item in sit { __ex_cmd.arg((&Arg(&__ex_item)).to_arg()); } __ex_cmd }",
+            )
+        );
+    }
+
+    #[test]
+    fn test_cmd_literal() {
+        assert_eq!(
+            ex_cmd_impl(r#""lorem\tipsum\x20{{dolor}}\
+                sit""#),
+            concat!(
+                "{ use ::expression_format::__private::{Arg, ToArg as _, ToDisplayArg as _};",
+                r#"let mut __ex_cmd = ::std::process::Command::new("lorem");"#,
+                r#"__ex_cmd.arg("ipsum");__ex_cmd.arg("{dolor}sit"); __ex_cmd }"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_cmd_errors() {
+        assert_eq!(
+            ex_cmd_impl(r#""lorem -{..ipsum}""#),
+            r#"::std::compile_error!("`{..ipsum}` must be a separate argument")"#
+        );
+        assert_eq!(ex_cmd_impl(r#"" ""#), r#"::std::compile_error!("`ex_cmd!` needs a program")"#);
+        assert_eq!(ex_cmd_impl(""), r#"::std::compile_error!("`ex_cmd!` expects a command template")"#);
+        assert_eq!(ex_cmd_impl("program"), r#"::std::compile_error!("`ex_cmd!` expects a command template")"#);
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            ex_message_impl("panic", &[], r#""lorem {ipsum}""#),
+            r#"panic!("{}",::std::format_args!("lorem {}",ipsum))"#
+        );
+    }
+
+    #[test]
+    fn test_message_with_arguments() {
+        assert_eq!(
+            ex_message_impl("assert", &["lorem".to_string()], r#""{{ipsum}}""#),
+            r#"assert!(lorem,"{}",::std::format_args!("{{ipsum}}"))"#
+        );
+    }
+
+    #[test]
+    fn test_no_message() {
+        assert_eq!(ex_message_impl("todo", &[], ""), "todo!()");
+    }
+
+    #[test]
+    fn test_empty_placeholder_argument() {
+        assert_eq!(
+            ex_impl_with_args("format", &[], r#""{lorem} {} {:?}""#, Some("dolor")),
+            r#"format!("{} {dolor} {dolor:?}",lorem)"#
+        );
+    }
+
+    #[test]
+    fn test_dbg_value() {
+        assert_eq!(
+            ex_dbg_impl(&[r#""lorem {:?}""#.to_string()], " ipsum"),
+            r#"match ipsum { __ex_dbg_value => { ::std::eprintln!("[{}:{}] {}", ::std::file!(), ::std::line!(), ::std::format_args!("lorem {__ex_dbg_value:?}")); __ex_dbg_value } }"#
+        );
+    }
+
+    #[test]
+    fn test_dbg_without_value() {
+        assert_eq!(
+            ex_dbg_impl(&[], r#""lorem {ipsum}""#),
+            r#"::std::eprintln!("[{}:{}] {}", ::std::file!(), ::std::line!(), ::std::format_args!("lorem {}",ipsum))"#
+        );
+    }
+
+    #[test]
+    fn test_span_marker() {
+        assert_eq!(span::parse_marker("__ex_span_3_10"), Some((false, 3..10)));
+        assert_eq!(span::parse_marker("__ex_error_0_1"), Some((true, 0..1)));
+        assert_eq!(span::parse_marker("__ex_count0"), None);
+    }
+
+    #[test]
+    fn test_span_snippet() {
+        let template = "\"lorem\n\tipsum {dolor\n sit\"";
+        assert_eq!(span::snippet(template, 14..15), "2 | \tipsum {dolor\n  | \t      ^");
+        assert_eq!(span::snippet(template, 1..6), "1 | \"lorem\n  |  ^^^^^");
+    }
+
+    #[test]
+    fn test_unspaced_spec() {
+        let template = "{:.2f} {:?v} {:>8 x} {:bx} {:?=y}";
+        let errors = |strict| -> Vec<_> {
+            syntax::parse(template)
+                .iter()
+                .filter_map(|segment| match segment {
+                    syntax::Segment::Placeholder(placeholder) => span::unspaced_spec(template, placeholder, strict),
+                    syntax::Segment::Text(_) => None,
+                })
+                .collect()
+        };
+        let ranges = |strict| errors(strict).into_iter().map(|(_, range)| range).collect::<Vec<_>>();
+
+        assert_eq!(
+            errors(false)[0].0,
+            "the format spec `:.2` is directly followed by `f`, add a space after the spec: `{:.2 f}`"
+        );
+        assert_eq!(ranges(false), [1..5, 22..25]);
+        assert_eq!(ranges(true), [1..5, 8..11, 22..25]);
+    }
+
+    #[test]
+    fn test_missing_positional() {
+        assert_eq!(
+            ex_impl("format", r#""{:04x}""#),
+            r#"::std::compile_error!("`{:04x}` formats a positional argument but there's none, write `{:04 x}` to format `x`")"#
+        );
+        assert_eq!(
+            ex_impl("format", r#""{:x}""#),
+            r#"::std::compile_error!("`{:x}` formats a positional argument but there's none, write `{x}` to format `x`")"#
+        );
+        test_helper(r#""{:04x}", 27"#, r#""{0:04x}",27"#);
+    }
+
+    fn test_helper(in_arg: &str, out_arg: &str) {
+        let expected = format!("format!({})", out_arg);
+        assert_eq!(ex_impl("format", in_arg), expected);
+    }
+}
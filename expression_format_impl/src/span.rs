@@ -0,0 +1,347 @@
+// locates the expressions and the errors of the generated code inside the template literal
+//
+// while `expand` generates the code, the expressions are written as `__ex_span_S_E(expr)` and
+// the errors as `__ex_error_S_E!("message")`, where `S..E` is their range in the template, the
+// markers are then replaced with tokens located at that range, or at the whole literal when
+// `Literal::subspan` isn't available, in which case the errors quote the template instead
+
+use expression_format_syntax as syntax;
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::cell::Cell;
+use std::ops::Range;
+
+thread_local! {
+    static MARKED: Cell<bool> = const { Cell::new(false) };
+}
+
+// `code` as written in the generated code for the range of `template` it comes from
+pub fn mark(code: &str, range: Range<usize>) -> String {
+    if MARKED.with(Cell::get) {
+        format!("__ex_span_{}_{}({})", range.start, range.end, code)
+    } else {
+        code.to_string()
+    }
+}
+
+// a compile error for the range of the template
+pub fn error_at(message: &str, range: Range<usize>) -> String {
+    if MARKED.with(Cell::get) {
+        format!("__ex_error_{}_{}!({:?})", range.start, range.end, message)
+    } else {
+        crate::compile_error(message)
+    }
+}
+
+// generates the code for `item` and locates it in its template, which is the first argument
+// after `args` that is a string literal
+pub fn expand<F>(item: TokenStream, args: usize, generate: F) -> TokenStream
+where
+    F: FnOnce(TokenStream) -> String,
+{
+    expand_template(item, args, false, generate)
+}
+
+// same as `expand` for a dedented template, it's located as a whole since the ranges refer to the
+// dedented text
+pub fn expand_dedented<F>(item: TokenStream, args: usize, generate: F) -> TokenStream
+where
+    F: FnOnce(TokenStream) -> String,
+{
+    expand_template(item, args, true, generate)
+}
+
+fn expand_template<F>(item: TokenStream, args: usize, dedented: bool, generate: F) -> TokenStream
+where
+    F: FnOnce(TokenStream) -> String,
+{
+    let (strict, item) = strip_strict(item);
+    let template = find_template(&item, args).map(|literal| {
+        let text = literal.to_string();
+        let text = if dedented { crate::dedent(&text) } else { text };
+        Template { literal, text, dedented, strict }
+    });
+
+    if let Some(error) = template.as_ref().and_then(|template| template.check_brackets().or_else(|| template.check_specs())) {
+        return error;
+    }
+
+    MARKED.with(|marked| marked.set(true));
+    let code = generate(item);
+    MARKED.with(|marked| marked.set(false));
+
+    match (code.parse(), &template) {
+        (Ok(tokens), Some(template)) => template.respan(tokens),
+        (Ok(tokens), None) => unmark(tokens),
+        (Err(_), Some(template)) => template.error("the template has an invalid expression", 0..template.text.len()),
+        (Err(_), None) => crate::compile_error("the template has an invalid expression").parse().unwrap(),
+    }
+}
+
+// removes `#[strict]` from the start of `item`, returning whether it was there
+fn strip_strict(item: TokenStream) -> (bool, TokenStream) {
+    let tokens: Vec<TokenTree> = item.clone().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Punct(pound), TokenTree::Group(group), rest @ ..]
+            if pound.as_char() == '#'
+                && group.delimiter() == Delimiter::Bracket
+                && group.stream().to_string() == "strict" =>
+        {
+            (true, rest.iter().cloned().collect())
+        }
+        _ => (false, item),
+    }
+}
+
+// the first argument after `args` that is only a string literal
+fn find_template(item: &TokenStream, args: usize) -> Option<Literal> {
+    let tokens: Vec<TokenTree> = item.clone().into_iter().collect();
+    tokens
+        .split(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == ','))
+        .skip(args)
+        .find_map(|argument| match argument {
+            [TokenTree::Literal(literal)] if is_string(literal) => Some(literal.clone()),
+            // a literal passed through a `macro_rules!` fragment
+            [TokenTree::Group(group)] if group.delimiter() == Delimiter::None => find_template(&group.stream(), 0),
+            _ => None,
+        })
+}
+
+fn is_string(literal: &Literal) -> bool {
+    let text = literal.to_string();
+    text.starts_with('"') || text.starts_with("r\"") || text.starts_with("r#")
+}
+
+struct Template {
+    literal: Literal,
+    // the template as parsed by the generators
+    text: String,
+    dedented: bool,
+    // `#[strict]` was written before the arguments
+    strict: bool,
+}
+
+impl Template {
+    // a `{` without its `}` or a lone `}` in the text between the placeholders
+    fn check_brackets(&self) -> Option<TokenStream> {
+        for segment in syntax::parse(&self.text) {
+            let span = match segment {
+                syntax::Segment::Text(span) => span,
+                syntax::Segment::Placeholder(_) => continue,
+            };
+
+            let mut chars = self.text[span.clone()].char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                let message = match c {
+                    '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                        chars.next();
+                        continue;
+                    }
+                    '{' => "unclosed `{` in the template",
+                    '}' => "unmatched `}` in the template, use `}}` to write it",
+                    _ => continue,
+                };
+
+                let start = span.start + i;
+                return Some(self.error(message, start..start + 1));
+            }
+        }
+
+        None
+    }
+
+    // a format spec without a space before its expression, see `unspaced_spec`
+    fn check_specs(&self) -> Option<TokenStream> {
+        syntax::parse(&self.text).iter().find_map(|segment| match segment {
+            syntax::Segment::Placeholder(placeholder) => {
+                let (message, range) = unspaced_spec(&self.text, placeholder, self.strict)?;
+                Some(self.error(&message, range))
+            }
+            syntax::Segment::Text(_) => None,
+        })
+    }
+
+    // the span of `range`, and whether it's exact
+    fn locate(&self, range: Range<usize>) -> (Span, bool) {
+        if !self.dedented {
+            if let Some(span) = subspan(&self.literal, range) {
+                return (span, true);
+            }
+        }
+
+        (self.literal.span(), false)
+    }
+
+    fn error(&self, message: &str, range: Range<usize>) -> TokenStream {
+        let (span, exact) = self.locate(range.clone());
+        let message = if exact { message.to_string() } else { format!("{}\n{}", message, snippet(&self.text, range)) };
+
+        let tokens: Vec<TokenTree> = vec![
+            Punct::new(':', Spacing::Joint).into(),
+            Punct::new(':', Spacing::Alone).into(),
+            Ident::new("std", span).into(),
+            Punct::new(':', Spacing::Joint).into(),
+            Punct::new(':', Spacing::Alone).into(),
+            Ident::new("compile_error", span).into(),
+            Punct::new('!', Spacing::Alone).into(),
+            Group::new(Delimiter::Parenthesis, TokenTree::from(Literal::string(&message)).into()).into(),
+        ];
+
+        tokens.into_iter().map(|token| with_span(token, span)).collect()
+    }
+
+    fn respan(&self, tokens: TokenStream) -> TokenStream {
+        let mut result = Vec::new();
+        let mut tokens = tokens.into_iter().peekable();
+
+        while let Some(token) = tokens.next() {
+            let marker = match &token {
+                TokenTree::Ident(ident) => parse_marker(&ident.to_string()),
+                TokenTree::Group(group) => {
+                    let mut respanned = Group::new(group.delimiter(), self.respan(group.stream()));
+                    respanned.set_span(group.span());
+                    result.push(respanned.into());
+                    continue;
+                }
+                _ => None,
+            };
+
+            match (marker, tokens.peek()) {
+                (Some((false, range)), Some(TokenTree::Group(_))) => {
+                    let (span, _) = self.locate(range);
+                    if let Some(TokenTree::Group(group)) = tokens.next() {
+                        let inner = self.respan(group.stream()).into_iter().map(|token| locate(token, span)).collect();
+                        let mut located = Group::new(Delimiter::None, inner);
+                        located.set_span(span);
+                        result.push(located.into());
+                    }
+                }
+                (Some((true, range)), Some(TokenTree::Punct(_))) => {
+                    tokens.next();
+                    if let Some(TokenTree::Group(group)) = tokens.next() {
+                        let message = group.stream().to_string();
+                        let message = crate::unescape(&message[1..message.len() - 1]);
+                        result.extend(self.error(&message, range));
+                    }
+                }
+                _ => result.push(token),
+            }
+        }
+
+        result.into_iter().collect()
+    }
+}
+
+// the error and its range for a spec directly followed by its expression when the first
+// characters of the expression could also end the spec, like `f` in `{:.2f}` or `x` in `{:bx}`,
+// when `strict` a space or `=` is needed after every spec followed by an expression
+pub fn unspaced_spec(template: &str, placeholder: &syntax::Placeholder, strict: bool) -> Option<(String, Range<usize>)> {
+    let spec = placeholder.spec.as_ref()?;
+    let spec_text = &template[spec.span.clone()];
+    let next = template[spec.span.end..].chars().next()?;
+
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let unspaced = if strict {
+        !next.is_whitespace() && next != '=' && next != '}'
+    } else {
+        spec_text.ends_with(is_word) && is_word(next)
+    };
+    if !unspaced {
+        return None;
+    }
+
+    let expr = template[placeholder.expr.clone()].trim_end();
+    let message = format!("the format spec `{0}` is directly followed by `{1}`, add a space after the spec: `{{{0} {1}}}`", spec_text, expr);
+    Some((message, spec.span.start..spec.span.end + next.len_utf8()))
+}
+
+// `(is_error, range)` of `__ex_span_S_E` or `__ex_error_S_E`
+pub fn parse_marker(ident: &str) -> Option<(bool, Range<usize>)> {
+    let (is_error, range) = match (ident.strip_prefix("__ex_span_"), ident.strip_prefix("__ex_error_")) {
+        (Some(range), _) => (false, range),
+        (_, Some(range)) => (true, range),
+        _ => return None,
+    };
+
+    let (start, end) = range.split_once('_')?;
+    Some((is_error, start.parse().ok()?..end.parse().ok()?))
+}
+
+// removes the markers without a template to locate them in
+fn unmark(tokens: TokenStream) -> TokenStream {
+    let mut result = Vec::new();
+    let mut tokens = tokens.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) => match parse_marker(&ident.to_string()) {
+                Some((true, _)) => {
+                    tokens.next();
+                    if let Some(TokenTree::Group(group)) = tokens.next() {
+                        result.extend(format!("::std::compile_error!{}", group).parse::<TokenStream>().unwrap());
+                    }
+                }
+                Some((false, _)) => {
+                    if let Some(TokenTree::Group(group)) = tokens.next() {
+                        result.push(Group::new(Delimiter::None, unmark(group.stream())).into());
+                    }
+                }
+                None => result.push(ident.into()),
+            },
+            TokenTree::Group(group) => {
+                let mut unmarked = Group::new(group.delimiter(), unmark(group.stream()));
+                unmarked.set_span(group.span());
+                result.push(unmarked.into());
+            }
+            token => result.push(token),
+        }
+    }
+
+    result.into_iter().collect()
+}
+
+#[cfg(feature = "nightly")]
+fn subspan(literal: &Literal, range: Range<usize>) -> Option<Span> {
+    literal.subspan(range)
+}
+
+#[cfg(not(feature = "nightly"))]
+fn subspan(_literal: &Literal, _range: Range<usize>) -> Option<Span> {
+    None
+}
+
+// keeps the hygiene of the generated token
+fn locate(token: TokenTree, span: Span) -> TokenTree {
+    match token {
+        TokenTree::Group(group) => {
+            let stream = group.stream().into_iter().map(|token| locate(token, span)).collect();
+            let mut located = Group::new(group.delimiter(), stream);
+            located.set_span(group.span().located_at(span));
+            located.into()
+        }
+        mut token => {
+            token.set_span(token.span().located_at(span));
+            token
+        }
+    }
+}
+
+fn with_span(mut token: TokenTree, span: Span) -> TokenTree {
+    token.set_span(span);
+    token
+}
+
+// the line of `range` in `template` with carets under the range
+pub fn snippet(template: &str, range: Range<usize>) -> String {
+    let start = template[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = template[range.start..].find('\n').map_or(template.len(), |i| range.start + i);
+    let line_number = template[..start].matches('\n').count() + 1;
+
+    let indent: String = template[start..range.start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = template[range.start..range.end.min(end)].chars().count().max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    format!("{} | {}\n{} | {}{}", line_number, &template[start..end], gutter, indent, "^".repeat(width))
+}
@@ -0,0 +1,642 @@
+//! Template parser of `expression_format`.
+//!
+//! [`parse`] splits a template into text and placeholders with the byte spans of their parts,
+//! using the grammar of the `expression_format` macros: `{{` and `}}` escapes, format specs
+//! followed by a space or after the expression in `{expr => :spec}`, `{=expr}` and rust
+//! expressions with nested brackets, strings, raw strings, chars, lifetimes and comments.
+//!
+//! # Example
+//! ```
+//! use expression_format_syntax::{parse, Count, Segment};
+//!
+//! let template = "Hello {:>width$ names[0]}!";
+//! let segments = parse(template);
+//! assert_eq!(segments.len(), 3);
+//!
+//! if let Segment::Placeholder(placeholder) = &segments[1] {
+//!     assert_eq!(&template[placeholder.span.clone()], "{:>width$ names[0]}");
+//!     assert_eq!(template[placeholder.expr.clone()].trim(), "names[0]");
+//!
+//!     let spec = placeholder.spec.as_ref().unwrap();
+//!     assert_eq!(&template[spec.span.clone()], ":>width$");
+//!     assert_eq!(spec.width, Some(Count::Named(9..14)));
+//! }
+//! ```
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+use std::str::CharIndices;
+
+/// Part of a template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    /// Text as written, with its `{{` and `}}` escapes.
+    Text(Range<usize>),
+    Placeholder(Placeholder),
+}
+
+/// A `{...}` with an embedded expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Placeholder {
+    /// From the opening `{` to the closing `}` included.
+    pub span: Range<usize>,
+    pub spec: Option<Spec>,
+    /// `{=expr}`, the expression is printed followed by `=` before its value.
+    pub self_doc: bool,
+    /// The expression with the whitespace around it, empty for `{}` and `{:?}`, without the `=>`
+    /// of a trailing spec.
+    pub expr: Range<usize>,
+}
+
+/// A format spec like `:>8.2` as understood by `format!`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Spec {
+    /// From the `:` to the end of the spec, before or after the expression.
+    pub span: Range<usize>,
+    pub fill: Option<char>,
+    pub align: Option<Align>,
+    pub sign: Option<Sign>,
+    /// `#`
+    pub alternate: bool,
+    /// `0`
+    pub zero: bool,
+    pub width: Option<Count>,
+    pub precision: Option<Count>,
+    /// One of `o`, `x`, `X`, `p`, `b`, `e` and `E`.
+    pub kind: Option<char>,
+    /// `?`
+    pub debug: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    /// `<`
+    Left,
+    /// `^`
+    Center,
+    /// `>`
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sign {
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+}
+
+/// Width or precision of a spec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Count {
+    /// `5`
+    Literal(usize),
+    /// `1$`
+    Positional(usize),
+    /// `name$`, with the span of the name.
+    Named(Range<usize>),
+    /// `*expr` or `*{expr}`, with the span of the expression.
+    Expr(Range<usize>),
+}
+
+/// Splits `template` into text and placeholders.
+///
+/// The segments alternate between text and placeholders, and start and end with text, which can
+/// be empty. A `{` without its closing `}` is left in the text, as `format!` reports it.
+pub fn parse(template: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut search_index = 0;
+
+    while let Some(placeholder) = range_in_brackets(template, search_index, &SPEC_REGEX) {
+        segments.push(Segment::Text(search_index..placeholder.span.start));
+        search_index = placeholder.span.end;
+        segments.push(Segment::Placeholder(placeholder));
+    }
+
+    segments.push(Segment::Text(search_index..template.len()));
+    segments
+}
+
+/// Splits `input` at the commas outside of brackets, strings and chars, like the arguments of a
+/// macro call.
+///
+/// The commas between generic arguments don't split it either, like in
+/// `HashMap::<u8, u8>::new()` or `<T as Trait<A, B>>::f()`, while a `<` elsewhere is taken as a
+/// comparison.
+pub fn split_arguments(input: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut iter = input.char_indices();
+    let mut prev_c = 0 as char;
+    let mut depth = 0;
+    let mut angles = 0;
+    let mut start = 0;
+
+    while let Some((i, c)) = iter.next() {
+        prev_c = match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                0 as char
+            }
+            ')' | ']' | '}' => {
+                depth -= 1;
+                0 as char
+            }
+            '<' if angles > 0 || input[..i].ends_with("::") || input[start..i].trim().is_empty() => {
+                angles += 1;
+                c
+            }
+            '>' if angles > 0 && prev_c != '-' => {
+                angles -= 1;
+                c
+            }
+            ',' if depth == 0 && angles == 0 => {
+                parts.push(&input[start..i]);
+                start = i + 1;
+                0 as char
+            }
+            'r' => try_raw_string(&mut iter, prev_c),
+            '"' => do_string(&mut iter),
+            '\'' => try_char(&mut iter),
+            _ => c,
+        };
+    }
+
+    parts.push(&input[start..]);
+    parts
+}
+
+type Iter<'a> = CharIndices<'a>;
+
+// compiled once, runtime templates are parsed here too
+static SPEC_REGEX: Lazy<SpecRegex> = Lazy::new(SpecRegex::new);
+
+struct SpecRegex {
+    flags: Regex,
+    count: Regex,
+    count_expr: Regex,
+    count_suffix: Regex,
+    kind: Regex,
+}
+
+impl SpecRegex {
+    fn new() -> Self {
+        SpecRegex {
+            flags: Regex::new(r#"^:(?:(.)?([<\^>]))?([\+\-])?(#)?(0)?"#).unwrap(),
+            count: Regex::new(r#"^(?:([A-Za-z_]\w*)\$|(\d+)(\$)?)"#).unwrap(),
+            count_expr: Regex::new(r#"^(?:[A-Za-z_]\w*|\d+)"#).unwrap(),
+            count_suffix: Regex::new(r#"^(?:\.[A-Za-z_]\w*(?:\(\))?|\[[^\[\]]*\])"#).unwrap(),
+            kind: Regex::new(r#"^([oxXpbeE])?(\?)?"#).unwrap(),
+        }
+    }
+}
+
+fn range_in_brackets(arg: &str, start_index: usize, specs: &SpecRegex) -> Option<Placeholder> {
+    let start = start_index + find_expr_start(&mut arg[start_index..].char_indices())?;
+    let placeholder = placeholder_at(arg, start, parse_spec(arg, start, specs, true), specs)?;
+
+    // a `*` count needs an expression after the spec, otherwise it's a dereference like `{:*ptr}`
+    let counts = placeholder.spec.as_ref().map_or([None, None], |spec| [spec.width.as_ref(), spec.precision.as_ref()]);
+    if counts.iter().any(|count| matches!(count, Some(Count::Expr(_)))) && arg[placeholder.expr.clone()].trim().is_empty() {
+        return placeholder_at(arg, start, parse_spec(arg, start, specs, false), specs);
+    }
+
+    Some(placeholder)
+}
+
+// the placeholder starting at `start` with the spec that follows it
+fn placeholder_at(arg: &str, start: usize, spec: Option<Spec>, specs: &SpecRegex) -> Option<Placeholder> {
+    let mut expr_start = spec.as_ref().map_or(start, |spec| spec.span.end);
+
+    let self_doc = arg[expr_start..].starts_with('=');
+    if self_doc {
+        expr_start += 1;
+    }
+
+    let end = expr_start + find_expr_end(&mut arg[expr_start..].char_indices())?;
+    let mut expr = expr_start..end;
+    let spec = spec.or_else(|| {
+        let (arrow, spec) = trailing_spec(arg, expr.clone(), specs)?;
+        expr.end = arrow;
+        Some(spec)
+    });
+
+    Some(Placeholder {
+        span: start - 1..end + 1,
+        spec,
+        self_doc,
+        expr,
+    })
+}
+
+// the start of the `=>` and the spec of `{expr => :spec}`, the spec has to reach the end of the
+// placeholder, up to whitespace
+fn trailing_spec(arg: &str, expr: Range<usize>, specs: &SpecRegex) -> Option<(usize, Spec)> {
+    let arrow = expr.start + find_spec_arrow(&mut arg[expr.clone()].char_indices())?;
+    let after = &arg[arrow + 2..expr.end];
+    let spec = parse_spec(&arg[..expr.end], expr.end - after.trim_start().len(), specs, true)?;
+
+    if arg[spec.span.end..expr.end].trim().is_empty() {
+        Some((arrow, spec))
+    } else {
+        None
+    }
+}
+
+// `expr_counts` allows the `*` width and precision
+fn parse_spec(arg: &str, start: usize, specs: &SpecRegex, expr_counts: bool) -> Option<Spec> {
+    let flags = specs.flags.captures(&arg[start..])?;
+    let mut index = start + flags[0].len();
+    let align = flags.get(2).map(|align| match align.as_str() {
+        "<" => Align::Left,
+        "^" => Align::Center,
+        _ => Align::Right,
+    });
+    let sign = flags.get(3).map(|sign| if sign.as_str() == "+" { Sign::Plus } else { Sign::Minus });
+
+    let width = parse_count(arg, &mut index, specs, expr_counts);
+    let mut precision = None;
+    if arg[index..].starts_with('.') {
+        let mut precision_index = index + 1;
+        precision = parse_count(arg, &mut precision_index, specs, expr_counts);
+        if precision.is_some() {
+            index = precision_index;
+        }
+    }
+
+    let kind = specs.kind.captures(&arg[index..]).unwrap();
+    let span = start..index + kind[0].len();
+
+    Some(Spec {
+        span,
+        fill: flags.get(1).and_then(|fill| fill.as_str().chars().next()),
+        align,
+        sign,
+        alternate: flags.get(4).is_some(),
+        zero: flags.get(5).is_some(),
+        width,
+        precision,
+        kind: kind.get(1).and_then(|kind| kind.as_str().chars().next()),
+        debug: kind.get(2).is_some(),
+    })
+}
+
+// width or precision: `5`, `1$`, `name$`, `*expr` or `*{expr}`, where `expr` without brackets
+// is limited to paths, fields, indexing and calls without arguments
+fn parse_count(arg: &str, index: &mut usize, specs: &SpecRegex, expr_counts: bool) -> Option<Count> {
+    let start = *index;
+    let count = &arg[start..];
+
+    if let Some(expr) = count.strip_prefix('*').filter(|_| expr_counts) {
+        let (range, len) = if let Some(block) = expr.strip_prefix('{') {
+            let end = find_expr_end(&mut block.char_indices())?;
+            (start + 2..start + 2 + end, end + 3)
+        } else {
+            let end = count_expr_end(expr, specs)?;
+            (start + 1..start + 1 + end, end + 1)
+        };
+
+        *index += len;
+        return Some(Count::Expr(range));
+    }
+
+    let found = specs.count.captures(count)?;
+    let count = match (found.get(1), found.get(2)) {
+        (Some(name), _) => Count::Named(start + name.start()..start + name.end()),
+        (None, Some(digits)) => {
+            let value = digits.as_str().parse().ok()?;
+            if found.get(3).is_some() {
+                Count::Positional(value)
+            } else {
+                Count::Literal(value)
+            }
+        }
+        (None, None) => return None,
+    };
+
+    *index += found[0].len();
+    Some(count)
+}
+
+// the end of a `*` count without brackets, which stops before a named precision like `.p$`
+fn count_expr_end(expr: &str, specs: &SpecRegex) -> Option<usize> {
+    let mut end = specs.count_expr.find(expr)?.end();
+    if expr.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some(end);
+    }
+
+    while let Some(suffix) = specs.count_suffix.find(&expr[end..]) {
+        let next = end + suffix.end();
+        if suffix.as_str().starts_with('.') && !suffix.as_str().ends_with(')') && expr[next..].starts_with('$') {
+            break;
+        }
+        end = next;
+    }
+
+    Some(end)
+}
+
+fn find_expr_start(iter: &mut Iter) -> Option<usize> {
+    let mut prev_c = 0 as char;
+    for (i, c) in iter {
+        if prev_c == '{' {
+            if c != '{' {
+                return Some(i)
+            } 
+            prev_c = 0 as char;
+        } else {
+            prev_c = c;
+        }
+    }
+
+    None
+}
+
+// counts opening and closing brackets and recognizes all the constructs
+// where they should be ignored
+fn find_expr_end(iter: &mut Iter) -> Option<usize> {
+    let mut prev_c = 0 as char;
+    let mut depth = 1;
+    while let Some((i, c)) = iter.next() {
+        prev_c = match c {
+            '{' => {
+                depth += 1;
+                0 as char
+            }
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+                0 as char
+            }
+            'r' => try_raw_string(iter, prev_c),
+            '"' => do_string(iter),
+            '/' => try_line_comment(iter, prev_c),
+            '*' => try_block_comment(iter, prev_c),
+            '\'' => try_char(iter),
+            _ => c,
+        };
+    }
+
+    None
+}
+
+// the last `=>` outside of brackets, which can't be part of the expression
+fn find_spec_arrow(iter: &mut Iter) -> Option<usize> {
+    let mut prev_c = 0 as char;
+    let mut depth = 0;
+    let mut arrow = None;
+    while let Some((i, c)) = iter.next() {
+        prev_c = match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                0 as char
+            }
+            ')' | ']' | '}' => {
+                depth -= 1;
+                0 as char
+            }
+            '>' if prev_c == '=' && depth == 0 => {
+                arrow = Some(i - 1);
+                0 as char
+            }
+            'r' => try_raw_string(iter, prev_c),
+            '"' => do_string(iter),
+            '/' => try_line_comment(iter, prev_c),
+            '*' => try_block_comment(iter, prev_c),
+            '\'' => try_char(iter),
+            _ => c,
+        };
+    }
+
+    arrow
+}
+
+fn try_raw_string(iter: &mut Iter, prev_c: char) -> char {
+    if prev_c.is_ascii_alphanumeric() || prev_c == '_' {
+        return 'r';
+    }
+    let mut depth = 0;
+    let iter_copy = iter.clone();
+    let mut prev_c = 'r';
+    for (_, c) in iter_copy {
+        if c == '"' {
+            iter.next();
+            do_raw_string(iter, depth);
+            break;
+        } else if c == '#' {
+            iter.next();
+            depth += 1;
+        } else {
+            return prev_c;
+        }
+
+        prev_c = c;
+    }
+
+    0 as char
+}
+
+fn do_raw_string(iter: &mut Iter, depth: usize) {
+    let mut depth_counter = depth;
+    let mut prev_c = 0 as char;
+    for (_, c) in iter {
+        if c == '"' && depth == 0 {
+            return;
+        } else if c == '#' {
+            if prev_c == '"' || depth_counter != depth {
+                depth_counter -= 1;
+                if depth_counter == 0 {
+                    return;
+                }
+            }
+        } else if depth_counter != depth {
+            depth_counter = depth;
+        }
+
+        prev_c = c;
+    }
+}
+
+fn do_string(iter: &mut Iter) -> char {
+    let mut prev_c = 0 as char;
+    for (_, c) in iter {
+        if c == '"' && prev_c != '\\' {
+            break;
+        }
+        prev_c = c;
+    }
+
+    0 as char
+}
+
+fn try_char(iter: &mut Iter) -> char {
+    if let Some((_, c)) = iter.next() {
+        if c.is_ascii_alphabetic() || c == '_' {
+            let iter_copy = iter.clone();
+
+            for (_, c) in iter_copy {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    if c == '\'' {
+                        iter.next();
+                    }
+
+                    return 0 as char;
+                }
+
+                iter.next();
+            }
+        } else {
+            let mut prev_c = c;
+            for (_, c) in iter {
+                if c == '\'' && prev_c != '\\' {
+                    return 0 as char;
+                }
+                prev_c = c;
+            }
+        }
+    }
+
+    0 as char
+}
+
+fn try_line_comment(iter: &mut Iter, prev_c: char) -> char {
+    if prev_c == '/' {
+        for (_, c) in iter {
+            if c == '\n' {
+                break;
+            }
+        }
+
+        return 0 as char;
+    }
+
+    '/'
+}
+
+fn try_block_comment(iter: &mut Iter, prev_c: char) -> char {
+    if prev_c == '/' {
+        let mut prev_c = 0 as char;
+        let mut depth = 1;
+        for (_, c) in iter {
+            if prev_c == '/' && c == '*' {
+                depth += 1;
+                prev_c = 0 as char;
+            } else if prev_c == '*' && c == '/' {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                prev_c = 0 as char;
+            } else {
+                prev_c = c;
+            }
+        }
+
+        return 0 as char;
+    }
+
+    '*'
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(template: &str) -> Vec<Placeholder> {
+        parse(template)
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Placeholder(placeholder) => Some(placeholder),
+                Segment::Text(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_segments() {
+        let segments = parse("{{a}} {b} c");
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], Segment::Text(0..6));
+        assert_eq!(segments[2], Segment::Text(9..11));
+
+        assert_eq!(parse("a {b"), vec![Segment::Text(0..4)]);
+        assert_eq!(parse(""), vec![Segment::Text(0..0)]);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let template = r##"{ "}" } {'}'} {r#"}"#} {/* } */ 1} {x::<'a>} { { 1 } }"##;
+        let exprs: Vec<_> = placeholders(template).into_iter().map(|p| &template[p.expr]).collect();
+        assert_eq!(exprs, [r#" "}" "#, "'}'", r##"r#"}"#"##, "/* } */ 1", "x::<'a>", " { 1 } "]);
+    }
+
+    #[test]
+    fn test_spec() {
+        let template = "{:_^+#010.*digits[1]x? value} {=a} {:?}";
+        let found = placeholders(template);
+
+        let spec = found[0].spec.clone().unwrap();
+        assert_eq!(&template[spec.span.clone()], ":_^+#010.*digits[1]x?");
+        assert_eq!(spec.fill, Some('_'));
+        assert_eq!(spec.align, Some(Align::Center));
+        assert_eq!(spec.sign, Some(Sign::Plus));
+        assert!(spec.alternate && spec.zero && spec.debug);
+        assert_eq!(spec.width, Some(Count::Literal(10)));
+        assert_eq!(spec.precision, Some(Count::Expr(11..20)));
+        assert_eq!(spec.kind, Some('x'));
+        assert_eq!(&template[found[0].expr.clone()], " value");
+
+        assert!(found[1].spec.is_none() && found[1].self_doc);
+        assert_eq!(&template[found[1].expr.clone()], "a");
+
+        assert_eq!(found[2].spec.as_ref().map(|spec| spec.debug), Some(true));
+        assert!(found[2].expr.is_empty());
+    }
+
+    #[test]
+    fn test_counts() {
+        let template = "{:1$.*{ x + 1 } a} {:w$ b}";
+        let found = placeholders(template);
+        let spec = found[0].spec.as_ref().unwrap();
+        assert_eq!(spec.width, Some(Count::Positional(1)));
+        assert_eq!(spec.precision, Some(Count::Expr(7..14)));
+        assert_eq!(found[1].spec.as_ref().unwrap().width, Some(Count::Named(21..22)));
+
+        let template = "{:*w.x$ v} {:*a.b[0].*c.d 1.5} {:*ptr} {:>*{p}}";
+        let found = placeholders(template);
+        let spec = found[0].spec.as_ref().unwrap();
+        assert_eq!((spec.width.clone(), spec.precision.clone()), (Some(Count::Expr(3..4)), Some(Count::Named(5..6))));
+        let spec = found[1].spec.as_ref().unwrap();
+        assert_eq!((spec.width.clone(), spec.precision.clone()), (Some(Count::Expr(14..20)), Some(Count::Expr(22..25))));
+        assert_eq!(&template[found[1].expr.clone()], " 1.5");
+        assert_eq!(&template[found[2].spec.as_ref().unwrap().span.clone()], ":");
+        assert_eq!(&template[found[2].expr.clone()], "*ptr");
+        assert_eq!(&template[found[3].expr.clone()], "*{p}");
+    }
+
+    #[test]
+    fn test_trailing_spec() {
+        let template = "{x => :>8} {=a[0] => :?} {match x { _ => 1 } => :x} {y => z} {:? b => :?}";
+        let found = placeholders(template);
+        let exprs: Vec<_> = found.iter().map(|p| &template[p.expr.clone()]).collect();
+        assert_eq!(exprs, ["x ", "a[0] ", "match x { _ => 1 } ", "y => z", " b => :?"]);
+
+        let specs: Vec<_> = found.iter().map(|p| p.spec.as_ref().map(|spec| &template[spec.span.clone()])).collect();
+        assert_eq!(specs, [Some(":>8"), Some(":?"), Some(":x"), None, Some(":?")]);
+        assert!(found[1].self_doc);
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        assert_eq!(split_arguments(r#""a, {b}", c(d, e), ',', f"#), [r#""a, {b}""#, " c(d, e)", " ','", " f"]);
+        assert_eq!(
+            split_arguments(r#""{}", HashMap::<u8, Vec<u8>>::new(), <A as B<C, D>>::f(), a < b, c > d"#),
+            [r#""{}""#, " HashMap::<u8, Vec<u8>>::new()", " <A as B<C, D>>::f()", " a < b", " c > d"]
+        );
+    }
+}
@@ -0,0 +1,28 @@
+// used by `ex_cmd!` to convert the value of each embedded expression into an argument,
+// `(&Arg(&value)).to_arg()` picks `ToArg` when the value is `AsRef<OsStr>` since it takes one
+// reference less, and `ToDisplayArg` otherwise
+
+use std::ffi::{OsStr, OsString};
+use std::fmt::Display;
+
+pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+pub trait ToArg {
+    fn to_arg(&self) -> OsString;
+}
+
+impl<T: AsRef<OsStr> + ?Sized> ToArg for Arg<'_, T> {
+    fn to_arg(&self) -> OsString {
+        self.0.as_ref().to_os_string()
+    }
+}
+
+pub trait ToDisplayArg {
+    fn to_arg(&self) -> OsString;
+}
+
+impl<T: Display + ?Sized> ToDisplayArg for &Arg<'_, T> {
+    fn to_arg(&self) -> OsString {
+        self.0.to_string().into()
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+
+/// Value returned by [`ex_display!`](macro.ex_display.html).
+///
+/// Formats by calling the wrapped closure, so the embedded expressions are evaluated every
+/// time the value is formatted and never before.
+#[derive(Clone, Copy)]
+pub struct LazyDisplay<F>(F);
+
+impl<F> LazyDisplay<F>
+where
+    F: Fn(&mut fmt::Formatter) -> fmt::Result,
+{
+    /// Wraps a closure that writes into a formatter.
+    pub fn new(f: F) -> Self {
+        LazyDisplay(f)
+    }
+}
+
+impl<F> fmt::Display for LazyDisplay<F>
+where
+    F: Fn(&mut fmt::Formatter) -> fmt::Result,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (self.0)(f)
+    }
+}
+
+impl<F> fmt::Debug for LazyDisplay<F>
+where
+    F: Fn(&mut fmt::Formatter) -> fmt::Result,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (self.0)(f)
+    }
+}
@@ -0,0 +1,53 @@
+// used by `ex_url!` to percent-encode the value of each embedded expression
+
+use std::fmt::{self, Write};
+
+// the part of the URL a value is in, each one keeps a different set of characters
+#[derive(Clone, Copy)]
+pub enum UrlComponent {
+    PathSegment,
+    QueryKey,
+    QueryValue,
+    Fragment,
+}
+
+impl UrlComponent {
+    // unreserved characters are always kept
+    fn keeps(self, b: u8) -> bool {
+        let extra: &[u8] = match self {
+            UrlComponent::PathSegment => b"!$&'()*+,;=:@",
+            UrlComponent::QueryKey => b"!$'()*,;:@/?",
+            UrlComponent::QueryValue => b"!$'()*,;:@/?=",
+            UrlComponent::Fragment => b"!$&'()*+,;=:@/?",
+        };
+        b.is_ascii_alphanumeric() || b"-._~".contains(&b) || extra.contains(&b)
+    }
+}
+
+// displays the wrapped value percent-encoded for the component
+pub struct Encode<T>(pub UrlComponent, pub T);
+
+impl<T: fmt::Display> fmt::Display for Encode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(EncodeWriter(self.0, f), "{}", self.1)
+    }
+}
+
+struct EncodeWriter<'a, 'b>(UrlComponent, &'a mut fmt::Formatter<'b>);
+
+impl Write for EncodeWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+        for (i, b) in s.bytes().enumerate() {
+            if !self.0.keeps(b) {
+                // non-ASCII bytes are never kept, so `start..i` is empty inside a character
+                if start < i {
+                    self.1.write_str(&s[start..i])?;
+                }
+                write!(self.1, "%{:02X}", b)?;
+                start = i + 1;
+            }
+        }
+        self.1.write_str(&s[start..])
+    }
+}
@@ -0,0 +1,139 @@
+//! Types used by [`ex_html!`](../macro.ex_html.html).
+
+use std::fmt::{self, Write};
+use std::ops::Deref;
+
+/// HTML returned by [`ex_html!`](../macro.ex_html.html).
+///
+/// Displays its contents as they are, and a `SafeHtml` embedded in another `ex_html!` like
+/// `{fragment}` isn't escaped again. This holds for a value dereferencing to a `SafeHtml` once,
+/// like a `&SafeHtml`, a `Box<SafeHtml>` or an `Rc<SafeHtml>`, other wrappers are escaped as any
+/// other value.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SafeHtml(String);
+
+impl SafeHtml {
+    /// Wraps a string that is already valid HTML, without escaping it.
+    pub fn from_trusted(html: String) -> Self {
+        SafeHtml(html)
+    }
+
+    /// Returns the HTML as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the HTML as a string.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for SafeHtml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+impl AsRef<str> for SafeHtml {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<SafeHtml> for String {
+    fn from(html: SafeHtml) -> Self {
+        html.0
+    }
+}
+
+// a value of `ex_html!` with the closure writing it with its format spec, `(&&value).html()`
+// displays a `SafeHtml` as it is and escapes any other value
+#[doc(hidden)]
+pub struct Value<R, F>(R, F);
+
+impl<'a, T: ?Sized, F> Value<&'a T, F>
+where
+    F: Fn(&mut dyn Write, &T) -> fmt::Result,
+{
+    pub fn new(value: &'a T, write: F) -> Self {
+        Value(value, write)
+    }
+}
+
+#[doc(hidden)]
+pub trait SafeValue {
+    type Output;
+    fn html(self) -> Self::Output;
+}
+
+impl<'b, 'a, F> SafeValue for &&'b Value<&'a SafeHtml, F> {
+    type Output = Html<'b, &'a SafeHtml, F>;
+    fn html(self) -> Self::Output {
+        Html(self, false)
+    }
+}
+
+// `&SafeHtml`, `Box<SafeHtml>`, `Rc<SafeHtml>`, ...
+impl<'b, 'a, T, F> SafeValue for &&'b Value<&'a T, F>
+where
+    T: Deref<Target = SafeHtml>,
+{
+    type Output = Html<'b, &'a T, F>;
+    fn html(self) -> Self::Output {
+        Html(self, false)
+    }
+}
+
+#[doc(hidden)]
+pub trait EscapedValue {
+    type Output;
+    fn html(self) -> Self::Output;
+}
+
+impl<'b, R, F> EscapedValue for &'b Value<R, F> {
+    type Output = Html<'b, R, F>;
+    fn html(self) -> Self::Output {
+        Html(self, true)
+    }
+}
+
+// displays the value, with `&`, `<`, `>`, `"` and `'` escaped when the flag is set
+#[doc(hidden)]
+pub struct Html<'b, R, F>(&'b Value<R, F>, bool);
+
+impl<T: ?Sized, F> fmt::Display for Html<'_, &T, F>
+where
+    F: Fn(&mut dyn Write, &T) -> fmt::Result,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Value(value, write) = self.0;
+        if self.1 {
+            write(&mut EscapeWriter(f), value)
+        } else {
+            write(f, value)
+        }
+    }
+}
+
+struct EscapeWriter<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl Write for EscapeWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            let escaped = match c {
+                '&' => "&amp;",
+                '<' => "&lt;",
+                '>' => "&gt;",
+                '"' => "&quot;",
+                '\'' => "&#39;",
+                _ => continue,
+            };
+            self.0.write_str(&s[start..i])?;
+            self.0.write_str(escaped)?;
+            start = i + 1;
+        }
+        self.0.write_str(&s[start..])
+    }
+}
@@ -19,8 +19,37 @@
 //! assert_eq!(ex_format!("{:.5 12.3}"), "12.30000");
 //! assert_eq!(ex_format!("{:#010x 27}!"), "0x0000001b!");
 //! ```
-//! No support for `*` and `$` parameters.
-//! 
+//!
+//! A spec directly followed by a word, like `{:.2f}` or `{:04x}` without a positional argument,
+//! is a compile error suggesting the spaced form. Starting the arguments with `#[strict]` makes a
+//! space, or the `=` of `{:?=expr}`, needed after every spec followed by an expression.
+//! ```
+//! use expression_format::ex_format;
+//! let v = [1];
+//! assert_eq!(ex_format!(#[strict] "{:? v} {:?=v}"), "[1] v=[1]");
+//! ```
+//!
+//! ---
+//!
+//! Width and precision from a variable with `$` or from any expression with `*`.
+//! ```
+//! use expression_format::ex_format;
+//! let width = 6;
+//! let digits = [3, 1];
+//! assert_eq!(ex_format!("{:>width$ 1.5}|"), "   1.5|");
+//! assert_eq!(ex_format!("{:.*digits[1] 1.25}|"), "1.2|");
+//! assert_eq!(ex_format!("{:^*{width + 2}.*{digits[0]} 0.5}|"), " 0.500  |");
+//! ```
+//!
+//! ---
+//!
+//! The spec can also follow the expression after `=>`, like in `format!`.
+//! ```
+//! use expression_format::ex_format;
+//! let width = 6;
+//! assert_eq!(ex_format!("{width * 2 => :>5}|{width as f32 / 4.0 => :.2}"), "   12|1.50");
+//! ```
+//!
 //! ---
 //! 
 //! Printing the contents of fields.
@@ -62,12 +91,91 @@
 //!
 //! ---
 //!
+//! Positional and named arguments after the string like in `format!`, empty placeholders
+//! and `N$` widths and precisions refer to the positional ones.
+//! ```
+//! use expression_format::ex_format;
+//! let (x, y) = (1, 2);
+//! assert_eq!(ex_format!("{a} + {b} = {}", x + y, a = x, b = y), "1 + 2 = 3");
+//! assert_eq!(ex_format!("{x} {:>3} {:?y}", "z"), "1   z 2");
+//! ```
+//!
+//! ---
+//!
+//! Print the expression itself followed by `=` and its value, like `{expr=}` in Python.
+//! ```
+//! use expression_format::ex_format;
+//! let items = vec![1, 2];
+//! assert_eq!(ex_format!("{=items.len()}, {:?=items}"), "items.len()=2, items=[1, 2]");
+//! ```
+//!
+//! ---
+//!
 //! Escape brackets with `{{` and `}}`.
 //! ```
 //! use expression_format::short::exf;
 //! let value = 10;
 //! assert_eq!(exf!("{{value}} = {value}"), "{value} = 10");
 //! ```
+//!
+//! ---
+//!
+//! Errors the macros find in a template, like `{:.2f}` needing a space, point at its
+//! string literal and quote the line of the faulty placeholder. Errors of the compiler in an
+//! embedded expression, like a type mismatch, point at the whole literal on stable. With the
+//! `nightly` feature and a nightly compiler both point at the expression inside the literal,
+//! except in the dedented templates of `ex_formatdoc!` and the like.
+
+// lets the generated `::expression_format` paths resolve inside this crate too
+extern crate self as expression_format;
+
+mod cmd;
+mod display;
+mod encode;
+#[cfg(feature = "derive")]
+mod error;
+pub mod html;
+#[cfg(feature = "runtime")]
+pub mod runtime;
+pub mod scan;
+pub mod sql;
+
+pub use display::LazyDisplay;
+
+/// Parser of the templates, shared by the macros and [`runtime::Template`](runtime/struct.Template.html).
+///
+/// # Example
+/// ```
+/// use expression_format::syntax::{self, Segment};
+///
+/// let template = "lorem {:>5 ipsum} dolor {=sit}";
+/// let exprs: Vec<_> = syntax::parse(template)
+///     .into_iter()
+///     .filter_map(|segment| match segment {
+///         Segment::Placeholder(placeholder) => Some(template[placeholder.expr].trim()),
+///         Segment::Text(_) => None,
+///     })
+///     .collect();
+/// assert_eq!(exprs, ["ipsum", "sit"]);
+/// ```
+pub use expression_format_syntax as syntax;
+
+// used by the generated code
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::cmd::{Arg, ToArg, ToDisplayArg};
+    pub use crate::encode::{Encode, UrlComponent};
+    #[cfg(feature = "derive")]
+    pub use crate::error::AsDynError;
+    #[cfg(feature = "log")]
+    pub use log;
+    #[cfg(feature = "tracing")]
+    pub use tracing;
+    #[cfg(feature = "url")]
+    pub use url;
+    #[cfg(feature = "anyhow")]
+    pub use anyhow;
+}
 
 /// Formats and prints to std error any valid rust expression in a string.
 ///
@@ -80,14 +188,319 @@ pub use expression_format_impl::ex_eprintln;
 /// Formats any valid rust expression in a string.
 ///
 /// Same as [`format!`](https://doc.rust-lang.org/std/macro.format.html) but with embedded parameters.
+/// It also takes positional and named arguments after the string, empty placeholders refer to the
+/// positional arguments and placeholders with only an argument name refer to the named ones.
 ///
 /// # Example
 /// ```
 /// use expression_format::ex_format;
 /// let arg = "ipsum";
 /// assert_eq!(ex_format!("lorem {arg}"), "lorem ipsum");
+/// assert_eq!(ex_format!("{arg} {} {:?sit}", "dolor", sit = 'a'), "ipsum dolor 'a'");
 /// ```
 pub use expression_format_impl::ex_format;
+/// Formats any valid rust expression in a multi-line string with its common indentation removed.
+///
+/// Same as [`ex_format!`](macro.ex_format.html), but the line break right after the opening quote
+/// and the leading whitespace common to all the lines are removed first, like
+/// [`formatdoc!`](https://docs.rs/indoc/1/indoc/macro.formatdoc.html) in `indoc`.
+/// Lines with only whitespace don't count for the common indentation, and lines starting inside
+/// an embedded expression and the formatted values are left alone. Unlike `formatdoc!`, only the
+/// line breaks in the source start a line: `"lorem\n    ipsum"` on one line is kept as it is.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_formatdoc;
+/// let items = ["lorem", "ipsum"];
+/// let text = ex_formatdoc!("
+///     items:
+///       {items[0]}
+///       {items[1]}
+///     total: {items.len()}
+/// ");
+/// assert_eq!(text, "items:\n  lorem\n  ipsum\ntotal: 2\n");
+/// ```
+pub use expression_format_impl::ex_formatdoc;
+/// Prints any valid rust expression in a multi-line string with its common indentation removed.
+///
+/// Same as [`ex_print!`](macro.ex_print.html) with the indentation handling of
+/// [`ex_formatdoc!`](macro.ex_formatdoc.html).
+pub use expression_format_impl::ex_printdoc;
+/// Prints to std error any valid rust expression in a multi-line string with its common
+/// indentation removed.
+///
+/// Same as [`ex_eprint!`](macro.ex_eprint.html) with the indentation handling of
+/// [`ex_formatdoc!`](macro.ex_formatdoc.html).
+pub use expression_format_impl::ex_eprintdoc;
+/// Writes any valid rust expression in a multi-line string with its common indentation removed
+/// into a buffer.
+///
+/// Same as [`ex_write!`](macro.ex_write.html) with the indentation handling of
+/// [`ex_formatdoc!`](macro.ex_formatdoc.html).
+pub use expression_format_impl::ex_writedoc;
+/// Creates a [`fmt::Arguments`](https://doc.rust-lang.org/std/fmt/struct.Arguments.html) from any
+/// valid rust expression in a string without allocating.
+///
+/// Same as [`format_args!`](https://doc.rust-lang.org/std/macro.format_args.html) but with embedded parameters,
+/// so the result has the same lifetime restrictions and the expressions are evaluated right away.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_format_args;
+/// let arg = "ipsum";
+/// assert_eq!(std::fmt::format(ex_format_args!("lorem {arg}")), "lorem ipsum");
+/// ```
+pub use expression_format_impl::ex_format_args;
+/// Creates a value that formats any valid rust expression in a string when it's displayed.
+///
+/// Unlike [`ex_format_args!`](macro.ex_format_args.html) the result can be stored and passed around,
+/// and the expressions are evaluated each time the value is formatted instead of right away.
+/// Nothing is allocated. See [`LazyDisplay`](struct.LazyDisplay.html).
+///
+/// # Example
+/// ```
+/// use expression_format::ex_display;
+/// use std::cell::Cell;
+///
+/// let count = Cell::new(0);
+/// let message = ex_display!("count = {count.get()}");
+/// count.set(2);
+/// assert_eq!(message.to_string(), "count = 2");
+/// ```
+pub use expression_format_impl::ex_display;
+/// Derives `Display` from an `#[ex_display("...")]` attribute with embedded parameters.
+///
+/// The attribute takes the same arguments as [`ex_format!`](macro.ex_format.html). Structs have a
+/// single attribute, and each variant of an enum has its own. The fields can be used by name,
+/// and tuple fields as `_0`, `_1`, ..., all of them by reference. `self` is also available.
+/// Requires the `derive` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ExDisplay;
+///
+/// #[derive(ExDisplay)]
+/// #[ex_display("Point({self.x}, {:.1 y})")]
+/// struct Point {
+///     x: i32,
+///     y: f64,
+/// }
+///
+/// #[derive(ExDisplay)]
+/// enum Shape {
+///     #[ex_display("circle at {center} with radius {radius}")]
+///     Circle { center: Point, radius: u32 },
+///     #[ex_display("line from {_0} to {_1}")]
+///     Line(Point, Point),
+///     #[ex_display("nothing")]
+///     Empty,
+/// }
+///
+/// let center = Point { x: 1, y: 2.0 };
+/// assert_eq!(center.to_string(), "Point(1, 2.0)");
+/// assert_eq!(
+///     Shape::Circle { center, radius: 3 }.to_string(),
+///     "circle at Point(1, 2.0) with radius 3"
+/// );
+/// assert_eq!(Shape::Empty.to_string(), "nothing");
+/// ```
+#[cfg(feature = "derive")]
+pub use expression_format_impl::ExDisplay;
+/// Derives `Display` and `Error` from an `#[ex_error("...")]` attribute with embedded parameters.
+///
+/// The message works like [`#[derive(ExDisplay)]`](derive.ExDisplay.html), structs have a single
+/// attribute and each variant of an enum has its own.
+/// [`Error::source`](https://doc.rust-lang.org/std/error/trait.Error.html#method.source) returns
+/// the field marked with `#[source]` or `#[from]`, or else the field named `source`.
+/// A `#[from]` field also gets a `From` impl, so it must be the only field of its variant.
+/// Requires the `derive` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ExError;
+/// use std::error::Error;
+/// use std::path::PathBuf;
+///
+/// #[derive(Debug, ExError)]
+/// enum ConfigError {
+///     #[ex_error("file {:?path} not found (errno {code})")]
+///     NotFound { path: PathBuf, code: i32 },
+///     #[ex_error("invalid number")]
+///     Parse(#[from] std::num::ParseIntError),
+///     #[ex_error("reading {name}")]
+///     Io { name: String, #[source] error: std::io::Error },
+/// }
+///
+/// let error = ConfigError::NotFound { path: "a.toml".into(), code: 2 };
+/// assert_eq!(error.to_string(), r#"file "a.toml" not found (errno 2)"#);
+/// assert!(error.source().is_none());
+///
+/// let error = ConfigError::from("x".parse::<u8>().unwrap_err());
+/// assert_eq!(error.to_string(), "invalid number");
+/// assert_eq!(error.source().unwrap().to_string(), "invalid digit found in string");
+/// ```
+#[cfg(feature = "derive")]
+pub use expression_format_impl::ExError;
+/// Creates HTML from any valid rust expression in a string, escaping the value of each expression.
+///
+/// Works like [`ex_format!`](macro.ex_format.html), but `&`, `<`, `>`, `"` and `'` in the
+/// formatted values are replaced with HTML entities, except in the values that are already a
+/// [`SafeHtml`](html/struct.SafeHtml.html). Start an expression with `!` to insert any value
+/// without escaping, like `{!trusted}`. The literal parts of the string are never escaped.
+/// Returns a `SafeHtml` instead of a `String`.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_html;
+/// let name = "<b>Tom & Jerry</b>";
+/// let item = ex_html!("<li>{name}</li>");
+/// assert_eq!(item.as_str(), "<li>&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;</li>");
+/// assert_eq!(
+///     ex_html!("<ul>{item}</ul>").as_str(),
+///     "<ul><li>&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;</li></ul>"
+/// );
+/// ```
+pub use expression_format_impl::ex_html;
+/// Parses a string with a template and assigns the parsed values to the embedded places,
+/// the inverse of [`ex_format!`](macro.ex_format.html).
+///
+/// The first argument is the input, anything that is `AsRef<str>`. The text of the template must
+/// match the input exactly, and each placeholder like `{x}` or `{point.y}` takes the input up
+/// to the next text of the template, or to the end. The captured text is converted with
+/// [`FromStr`](https://doc.rust-lang.org/std/str/trait.FromStr.html) into the type of the place.
+///
+/// Format specs guide the parsing:
+///
+/// * a width like `{:4 x}` takes exactly that many characters and trims the padding around them,
+///   so consecutive placeholders need a width or text between them
+/// * `x`, `X`, `o` and `b` parse integers in that radix with
+///   [`FromStrRadix`](scan/trait.FromStrRadix.html), and `#` requires the `0x`, `0o` or `0b` prefix
+/// * alignment and `0` are accepted and ignored
+///
+/// An empty placeholder `{}` matches text without assigning it. Returns
+/// `Result<(), ScanError>`, and the places are only assigned if the whole input matches,
+/// see [`ScanError`](scan/enum.ScanError.html).
+///
+/// # Example
+/// ```
+/// use expression_format::ex_scan;
+/// use expression_format::scan::ScanError;
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let mut point = Point { x: 0, y: 0 };
+/// let mut name = String::new();
+/// let mut color = 0u32;
+///
+/// ex_scan!("3,-4 #ff8000 origin", "{point.x},{point.y} #{:6x color} {name}").unwrap();
+/// assert_eq!((point.x, point.y, color, name.as_str()), (3, -4, 0xff8000, "origin"));
+///
+/// let error = ex_scan!("3,a", "{point.x},{point.y}").unwrap_err();
+/// assert_eq!(error.to_string(), r#"invalid value "a" for `{point.y}` at byte 2: invalid digit found in string"#);
+/// ```
+pub use expression_format_impl::ex_scan;
+/// Creates a URL from any valid rust expression in a string, percent-encoding the value of each
+/// expression for the part of the URL it's in.
+///
+/// Works like [`ex_format!`](macro.ex_format.html), but the formatted values are encoded as a path
+/// segment before `?`, as a query key or value after `?`, and as a fragment after `#`.
+/// Reserved characters like `/`, `?`, `&`, `=` and `#` are encoded when they would change the
+/// structure of the URL, and so are spaces and non-ASCII characters.
+/// Start an expression with `!` to insert it without encoding, like a base URL in `{!base}`.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_url;
+/// let base = "https://example.com/api";
+/// let id = "a/b c";
+/// let query = "x&y=1";
+/// assert_eq!(
+///     ex_url!("{!base}/items/{id}?q={query}&{=id}#{id}"),
+///     "https://example.com/api/items/a%2Fb%20c?q=x%26y=1&id=a/b%20c#a/b%20c"
+/// );
+/// ```
+pub use expression_format_impl::ex_url;
+/// Same as [`ex_url!`](macro.ex_url.html) but parses the result as a
+/// [`url::Url`](https://docs.rs/url/2/url/struct.Url.html), returning a `Result<Url, ParseError>`.
+/// Requires the `url` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_url_parse;
+/// let user = "Jane Doe";
+/// let url = ex_url_parse!("https://example.com/users/{user}?tab=posts").unwrap();
+/// assert_eq!(url.path(), "/users/Jane%20Doe");
+/// assert_eq!(url.query(), Some("tab=posts"));
+/// ```
+#[cfg(feature = "url")]
+pub use expression_format_impl::ex_url_parse;
+/// Creates an SQL query from a string, with each embedded expression bound as a parameter.
+///
+/// Each expression is replaced with a placeholder and a reference to its value is added to the
+/// parameters of the returned [`Query`](sql/struct.Query.html), so values are never spliced
+/// into the query. Format specs aren't allowed.
+/// The placeholders depend on the dialect given before the string:
+///
+/// * none: `?`
+/// * `numbered`: `$1`, `$2`, ...
+/// * `named`: `:name`, named after the expression like `:user_id` for `{user.id}`, other
+///   expressions need a name with `{name = expr}`. Each name is a single parameter.
+///
+/// With the `rusqlite` feature the query can be run with
+/// [`Query::execute`](sql/struct.Query.html#method.execute) and
+/// [`Query::query_row`](sql/struct.Query.html#method.query_row).
+///
+/// # Example
+/// ```
+/// use expression_format::ex_sql;
+///
+/// struct User {
+///     id: u32,
+/// }
+///
+/// let user = User { id: 7 };
+/// let name = "Robert'); DROP TABLE users;--";
+///
+/// let query = ex_sql!("SELECT * FROM users WHERE id = {user.id} AND name = {name}");
+/// assert_eq!(query.sql, "SELECT * FROM users WHERE id = ? AND name = ?");
+/// assert_eq!(query.params, (&7, &name));
+///
+/// let query = ex_sql!(numbered, "UPDATE users SET name = {name} WHERE id = {user.id}");
+/// assert_eq!(query.sql, "UPDATE users SET name = $1 WHERE id = $2");
+///
+/// let query = ex_sql!(named, "SELECT * FROM users WHERE id = {user.id} OR parent = {user.id}");
+/// assert_eq!(query.sql, "SELECT * FROM users WHERE id = :user_id OR parent = :user_id");
+/// assert_eq!(query.params, (&7,));
+/// ```
+pub use expression_format_impl::ex_sql;
+/// Creates a [`Command`](https://doc.rust-lang.org/std/process/struct.Command.html) from a
+/// string with embedded parameters, without going through a shell.
+///
+/// The literal parts of the string are split on whitespace, and the first word is the program.
+/// The value of each expression is always part of a single argument, even if it contains
+/// whitespace or quotes, and is joined with the text around it like in `--name={name}`.
+/// Values that are `AsRef<OsStr>`, like strings and paths, are passed as they are, other values
+/// are formatted with `Display` or with the format spec.
+/// Collections are expanded into one argument per item with `{..expr}`.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_cmd;
+/// use std::path::Path;
+///
+/// let count = 3;
+/// let path = Path::new("src/my file.rs");
+/// let extra = ["--oneline", "--reverse"];
+///
+/// let cmd = ex_cmd!("git log -n {count} {..extra} -- {path}");
+/// let args: Vec<_> = cmd.get_args().collect();
+/// assert_eq!(cmd.get_program(), "git");
+/// assert_eq!(args, ["log", "-n", "3", "--oneline", "--reverse", "--", "src/my file.rs"]);
+/// ```
+pub use expression_format_impl::ex_cmd;
 /// Formats and prints to std out any valid rust expression in a string.
 ///
 /// Same as [`print!`](https://doc.rust-lang.org/std/macro.print.html) but with embedded parameters.
@@ -96,6 +509,340 @@ pub use expression_format_impl::ex_print;
 ///
 /// Same as [`println!`](https://doc.rust-lang.org/std/macro.println.html) but with embedded parameters.
 pub use expression_format_impl::ex_println;
+/// Formats and prints to std out any valid rust expression in a string, returning the error
+/// instead of panicking.
+///
+/// Same as [`ex_print!`](macro.ex_print.html) but returns `io::Result<()>`, so a closed pipe or a
+/// full disk can be handled. Stdout is locked while writing.
+///
+/// # Example
+/// ```
+/// use expression_format::try_ex_print;
+/// let name = "lorem";
+/// if let Err(error) = try_ex_print!("{name} ") {
+///     eprintln!("{}", error);
+/// }
+/// ```
+pub use expression_format_impl::try_ex_print;
+/// Formats and prints to std out any valid rust expression in a string with a new line at the end,
+/// returning the error instead of panicking.
+///
+/// Same as [`ex_println!`](macro.ex_println.html) but returns `io::Result<()>`, so a closed pipe
+/// or a full disk can be handled. Stdout is locked while writing.
+///
+/// # Example
+/// ```
+/// use expression_format::try_ex_println;
+///
+/// fn main() -> std::io::Result<()> {
+///     for i in 0..3 {
+///         match try_ex_println!("line {i}") {
+///             Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => break,
+///             result => result?,
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub use expression_format_impl::try_ex_println;
+/// Formats and prints to std error any valid rust expression in a string, returning the error
+/// instead of panicking.
+///
+/// Same as [`ex_eprint!`](macro.ex_eprint.html) but returns `io::Result<()>`.
+pub use expression_format_impl::try_ex_eprint;
+/// Formats and prints to std error any valid rust expression in a string with a new line at the
+/// end, returning the error instead of panicking.
+///
+/// Same as [`ex_eprintln!`](macro.ex_eprintln.html) but returns `io::Result<()>`.
+pub use expression_format_impl::try_ex_eprintln;
+/// Prints any valid rust expression in a multi-line string with its common indentation removed,
+/// returning the error instead of panicking.
+///
+/// Same as [`ex_printdoc!`](macro.ex_printdoc.html) but returns `io::Result<()>`.
+pub use expression_format_impl::try_ex_printdoc;
+/// Prints to std error any valid rust expression in a multi-line string with its common
+/// indentation removed, returning the error instead of panicking.
+///
+/// Same as [`ex_eprintdoc!`](macro.ex_eprintdoc.html) but returns `io::Result<()>`.
+pub use expression_format_impl::try_ex_eprintdoc;
+/// Prints any valid rust expression in a string to std error, prefixed with the file and line,
+/// and returns the value of the expression after the string.
+///
+/// Similar to [`dbg!`](https://doc.rust-lang.org/std/macro.dbg.html) but with embedded parameters.
+/// Empty placeholders like `{}`, `{:?}` or `{:#?}` refer to the returned value.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_dbg;
+/// let items = vec![1, 2, 3];
+/// let total = ex_dbg!("sum of {items.len()} items: {:?}", items.iter().sum::<i32>()) * 2;
+/// // stderr: [src/main.rs:4] sum of 3 items: 6
+/// assert_eq!(total, 12);
+/// ```
+pub use expression_format_impl::ex_dbg;
+/// Panics the current thread with any valid rust expression in a string as message.
+///
+/// Same as [`panic!`](https://doc.rust-lang.org/std/macro.panic.html) but with embedded parameters.
+/// The reported location is the call site, or the caller of a `#[track_caller]` function.
+///
+/// # Example
+/// ```should_panic
+/// use expression_format::ex_panic;
+/// let index = 3;
+/// ex_panic!("index {index} is out of range");
+/// ```
+pub use expression_format_impl::ex_panic;
+/// Indicates unreachable code with any valid rust expression in a string as message.
+///
+/// Same as [`unreachable!`](https://doc.rust-lang.org/std/macro.unreachable.html) but with embedded parameters.
+pub use expression_format_impl::ex_unreachable;
+/// Indicates unfinished code with any valid rust expression in a string as message.
+///
+/// Same as [`todo!`](https://doc.rust-lang.org/std/macro.todo.html) but with embedded parameters.
+pub use expression_format_impl::ex_todo;
+/// Indicates unimplemented code with any valid rust expression in a string as message.
+///
+/// Same as [`unimplemented!`](https://doc.rust-lang.org/std/macro.unimplemented.html) but with embedded parameters.
+pub use expression_format_impl::ex_unimplemented;
+/// Asserts that a boolean expression is `true`, with any valid rust expression in a string as
+/// the optional message.
+///
+/// Same as [`assert!`](https://doc.rust-lang.org/std/macro.assert.html) but with embedded parameters.
+/// The message is only formatted if the assertion fails.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_assert;
+/// let items = [1, 2, 3];
+/// ex_assert!(items.len() == 3, "unexpected items {:?items}");
+/// ```
+pub use expression_format_impl::ex_assert;
+/// Asserts that two expressions are equal, with any valid rust expression in a string as the
+/// optional message.
+///
+/// Same as [`assert_eq!`](https://doc.rust-lang.org/std/macro.assert_eq.html) but with embedded parameters.
+/// The message is only formatted if the assertion fails.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_assert_eq;
+/// let id = 7;
+/// ex_assert_eq!(id * 2, 14, "wrong double for id {id}");
+/// ```
+pub use expression_format_impl::ex_assert_eq;
+/// Asserts that two expressions are not equal, with any valid rust expression in a string as the
+/// optional message.
+///
+/// Same as [`assert_ne!`](https://doc.rust-lang.org/std/macro.assert_ne.html) but with embedded parameters.
+/// The message is only formatted if the assertion fails.
+pub use expression_format_impl::ex_assert_ne;
+/// Same as [`ex_assert!`](macro.ex_assert.html) but only enabled in non optimized builds by default.
+///
+/// See [`debug_assert!`](https://doc.rust-lang.org/std/macro.debug_assert.html).
+pub use expression_format_impl::ex_debug_assert;
+/// Same as [`ex_assert_eq!`](macro.ex_assert_eq.html) but only enabled in non optimized builds by default.
+///
+/// See [`debug_assert_eq!`](https://doc.rust-lang.org/std/macro.debug_assert_eq.html).
+pub use expression_format_impl::ex_debug_assert_eq;
+/// Same as [`ex_assert_ne!`](macro.ex_assert_ne.html) but only enabled in non optimized builds by default.
+///
+/// See [`debug_assert_ne!`](https://doc.rust-lang.org/std/macro.debug_assert_ne.html).
+pub use expression_format_impl::ex_debug_assert_ne;
+/// Writes any valid rust expression in a string into a buffer.
+///
+/// Same as [`write!`](https://doc.rust-lang.org/std/macro.write.html) but with embedded parameters.
+/// The destination can be anything with a `write_fmt` method, like implementors of
+/// [`fmt::Write`](https://doc.rust-lang.org/std/fmt/trait.Write.html) or
+/// [`io::Write`](https://doc.rust-lang.org/std/io/trait.Write.html).
+///
+/// # Example
+/// ```
+/// use expression_format::ex_write;
+/// use std::fmt;
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// impl fmt::Display for Point {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         ex_write!(f, "Point({self.x}, {self.y})")
+///     }
+/// }
+///
+/// assert_eq!(Point { x: 1, y: 2 }.to_string(), "Point(1, 2)");
+/// ```
+pub use expression_format_impl::ex_write;
+/// Writes any valid rust expression in a string into a buffer with a new line at the end.
+///
+/// Same as [`writeln!`](https://doc.rust-lang.org/std/macro.writeln.html) but with embedded parameters.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_writeln;
+/// use std::io::Write;
+///
+/// let mut out = Vec::new();
+/// let value = 42;
+/// ex_writeln!(&mut out, "value = {value}").unwrap();
+/// assert_eq!(out, b"value = 42\n");
+/// ```
+pub use expression_format_impl::ex_writeln;
+
+/// Logs any valid rust expression in a string at the given level.
+///
+/// Same as [`log!`](https://docs.rs/log/0.4/log/macro.log.html) but with embedded parameters,
+/// the expressions are only evaluated if the level is enabled. Requires the `log` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_log;
+/// use log::Level;
+/// let id = 7;
+/// ex_log!(target: "app", Level::Info, "user {id} logged in");
+/// ```
+#[cfg(feature = "log")]
+pub use expression_format_impl::ex_log;
+/// Logs any valid rust expression in a string at the error level.
+///
+/// Same as [`error!`](https://docs.rs/log/0.4/log/macro.error.html) but with embedded parameters.
+/// Requires the `log` feature.
+#[cfg(feature = "log")]
+pub use expression_format_impl::ex_error;
+/// Logs any valid rust expression in a string at the warn level.
+///
+/// Same as [`warn!`](https://docs.rs/log/0.4/log/macro.warn.html) but with embedded parameters.
+/// Requires the `log` feature.
+#[cfg(feature = "log")]
+pub use expression_format_impl::ex_warn;
+/// Logs any valid rust expression in a string at the info level.
+///
+/// Same as [`info!`](https://docs.rs/log/0.4/log/macro.info.html) but with embedded parameters,
+/// the expressions are only evaluated if the level is enabled. Requires the `log` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_info;
+/// let items = vec![1, 2, 3];
+/// ex_info!("processing {items.len()} items");
+/// ex_info!(target: "app::import", "items = {:?items}");
+/// ```
+#[cfg(feature = "log")]
+pub use expression_format_impl::ex_info;
+/// Logs any valid rust expression in a string at the debug level.
+///
+/// Same as [`debug!`](https://docs.rs/log/0.4/log/macro.debug.html) but with embedded parameters.
+/// Requires the `log` feature.
+#[cfg(feature = "log")]
+pub use expression_format_impl::ex_debug;
+/// Logs any valid rust expression in a string at the trace level.
+///
+/// Same as [`trace!`](https://docs.rs/log/0.4/log/macro.trace.html) but with embedded parameters.
+/// Requires the `log` feature.
+#[cfg(feature = "log")]
+pub use expression_format_impl::ex_trace;
+/// Emits a [`tracing`](https://docs.rs/tracing/0.1) event with any valid rust expression in a
+/// string as message and each expression as a field.
+///
+/// Similar to [`event!`](https://docs.rs/tracing/0.1/tracing/macro.event.html) but with embedded parameters.
+/// Fields are named after the expression when it's a path like `{count}` or `{user.id}`,
+/// with a leading `self.` removed, other expressions need a name with `{name = expr}`.
+/// Fields are recorded with `Display`, or with `Debug` if the format spec contains `?`.
+/// The expressions are only evaluated once, and only if the event is enabled.
+/// Requires the `tracing` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_event;
+/// use tracing::Level;
+///
+/// struct User {
+///     id: u32,
+/// }
+///
+/// let user = User { id: 7 };
+/// let items = vec!["apple", "pear"];
+/// // message: "user 7 bought 2 items: ["apple", "pear"]"
+/// // fields: user.id = 7, count = 2, items = ["apple", "pear"]
+/// ex_event!(Level::INFO, "user {user.id} bought {count = items.len()} items: {:?items}");
+/// ex_event!(target: "shop", Level::DEBUG, "user {user.id} logged out");
+/// ```
+#[cfg(feature = "tracing")]
+pub use expression_format_impl::ex_event;
+/// Creates an [`anyhow::Error`](https://docs.rs/anyhow/1/anyhow/struct.Error.html) with any valid
+/// rust expression in a string as message.
+///
+/// Same as [`anyhow!`](https://docs.rs/anyhow/1/anyhow/macro.anyhow.html) but with embedded parameters.
+/// Requires the `anyhow` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_anyhow;
+/// let path = "config.toml";
+/// let error = ex_anyhow!("missing {:?path}");
+/// assert_eq!(error.to_string(), r#"missing "config.toml""#);
+/// ```
+#[cfg(feature = "anyhow")]
+pub use expression_format_impl::ex_anyhow;
+/// Returns early with an [`anyhow::Error`](https://docs.rs/anyhow/1/anyhow/struct.Error.html) with
+/// any valid rust expression in a string as message.
+///
+/// Same as [`bail!`](https://docs.rs/anyhow/1/anyhow/macro.bail.html) but with embedded parameters.
+/// Requires the `anyhow` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_bail;
+///
+/// fn check(port: u32) -> anyhow::Result<u32> {
+///     if port > 65535 {
+///         ex_bail!("port {port} is out of range");
+///     }
+///     Ok(port)
+/// }
+///
+/// assert_eq!(check(70000).unwrap_err().to_string(), "port 70000 is out of range");
+/// ```
+#[cfg(feature = "anyhow")]
+pub use expression_format_impl::ex_bail;
+/// Returns early with an [`anyhow::Error`](https://docs.rs/anyhow/1/anyhow/struct.Error.html) if
+/// a condition isn't true, with any valid rust expression in a string as message.
+///
+/// Same as [`ensure!`](https://docs.rs/anyhow/1/anyhow/macro.ensure.html) but with embedded parameters.
+/// The message is only formatted if the condition is false.
+/// Requires the `anyhow` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_ensure;
+///
+/// fn check(items: &[u32]) -> anyhow::Result<()> {
+///     ex_ensure!(items.len() < 3, "too many items: {items.len()}");
+///     Ok(())
+/// }
+///
+/// assert_eq!(check(&[1, 2, 3]).unwrap_err().to_string(), "too many items: 3");
+/// ```
+#[cfg(feature = "anyhow")]
+pub use expression_format_impl::ex_ensure;
+/// Adds context to the error of a `Result` or to a `None`, with any valid rust expression in a
+/// string as message.
+///
+/// Same as [`Context::with_context`](https://docs.rs/anyhow/1/anyhow/trait.Context.html#tymethod.with_context)
+/// with a closure returning [`ex_format!`](macro.ex_format.html), so the message is only formatted
+/// on the error path. Requires the `anyhow` feature.
+///
+/// # Example
+/// ```
+/// use expression_format::ex_with_context;
+///
+/// let path = "missing.toml";
+/// let error = ex_with_context!(std::fs::read_to_string(path), "reading {:?path}").unwrap_err();
+/// assert_eq!(error.to_string(), r#"reading "missing.toml""#);
+/// ```
+#[cfg(feature = "anyhow")]
+pub use expression_format_impl::ex_with_context;
 
 /// Short name versions
 pub mod short {
@@ -103,12 +850,32 @@ pub mod short {
     pub use expression_format_impl::ex_eprint as exep;
     /// Short name version of [`ex_format!`](../macro.ex_format.html)
     pub use expression_format_impl::ex_format as exf;
+    /// Short name version of [`ex_format_args!`](../macro.ex_format_args.html)
+    pub use expression_format_impl::ex_format_args as exfa;
+    /// Short name version of [`ex_display!`](../macro.ex_display.html)
+    pub use expression_format_impl::ex_display as exds;
+    /// Short name version of [`ex_html!`](../macro.ex_html.html)
+    pub use expression_format_impl::ex_html as exh;
     /// Short name version of [`ex_print!`](../macro.ex_print.html)
     pub use expression_format_impl::ex_print as exp;
     /// Short name version of [`ex_println!`](../macro.ex_println.html)
     pub use expression_format_impl::ex_println as expl;
     /// Short name version of [`ex_eprintln!`](../macro.ex_eprintln.html)
     pub use expression_format_impl::ex_eprintln as exepl;
+    /// Short name version of [`ex_dbg!`](../macro.ex_dbg.html)
+    pub use expression_format_impl::ex_dbg as exd;
+    /// Short name version of [`ex_panic!`](../macro.ex_panic.html)
+    pub use expression_format_impl::ex_panic as expn;
+    /// Short name version of [`ex_unreachable!`](../macro.ex_unreachable.html)
+    pub use expression_format_impl::ex_unreachable as exur;
+    /// Short name version of [`ex_todo!`](../macro.ex_todo.html)
+    pub use expression_format_impl::ex_todo as extd;
+    /// Short name version of [`ex_unimplemented!`](../macro.ex_unimplemented.html)
+    pub use expression_format_impl::ex_unimplemented as exui;
+    /// Short name version of [`ex_write!`](../macro.ex_write.html)
+    pub use expression_format_impl::ex_write as exw;
+    /// Short name version of [`ex_writeln!`](../macro.ex_writeln.html)
+    pub use expression_format_impl::ex_writeln as exwl;
 }
 
 #[cfg(test)]
@@ -275,6 +1042,1087 @@ mod tests {
         assert_eq!(exf!("{:.5 12.3}"), "12.30000");
     }
 
+    #[test]
+    fn test_trailing_spec() {
+        let values = [1.5, 27.0];
+        assert_eq!(exf!("{values[0] => :>6.2}|{values[1] as u8 => :#x}"), "  1.50|0x1b");
+        assert_eq!(exf!(r#"{=match values.len() { 2 => "two", _ => "more" } => :?}"#), r#"match values.len() { 2 => "two", _ => "more" }="two""#);
+    }
+
+    #[test]
+    fn test_strict() {
+        use crate::short::exw;
+        use std::fmt::Write;
+
+        let mut s = String::new();
+        let value = 1.5;
+        exw!(#[strict] s, "{:.2 value} {:?=value} {value => :?}").unwrap();
+        assert_eq!(s, "1.50 value=1.5 1.5");
+        assert_eq!(exf!(#[strict] "{:.1 value}"), "1.5");
+    }
+
+    #[test]
+    fn test_width_argument() {
+        let width = 5;
+        assert_eq!(exf!(r#"{:>width$ "x"}!"#), "    x!");
+    }
+
+    #[test]
+    fn test_width_and_precision_arguments() {
+        let (w, p) = (8, 2);
+        assert_eq!(exf!("{:w$.p$ 12.3456}!"), "   12.35!");
+    }
+
+    #[test]
+    fn test_width_expression() {
+        let widths = [2, 6];
+        assert_eq!(exf!(r#"{:-<*widths[1] "x"}!"#), "x-----!");
+    }
+
+    #[test]
+    fn test_precision_expression() {
+        struct Column {
+            precision: usize,
+        }
+        let column = Column { precision: 3 };
+        assert_eq!(exf!("{:.*column.precision 1.0}"), "1.000");
+    }
+
+    #[test]
+    fn test_width_expression_and_precision() {
+        let (w, p) = (7, 2);
+        let widths = [6];
+        assert_eq!(exf!("{:*w.p$ 1.5}|{:*widths[0].*p 0.25}|"), "   1.50|  0.25|");
+    }
+
+    #[test]
+    fn test_dereference_without_spec() {
+        let value = &5;
+        assert_eq!(exf!("{:*value}|{:>3*value}"), "5|  5");
+    }
+
+    #[test]
+    fn test_width_and_precision_block_expressions() {
+        let name = "lorem";
+        assert_eq!(
+            exf!("[{:^*{name.len() + 4}.*{name.len() - 2} name}]"),
+            "[   lor   ]"
+        );
+    }
+
+    #[test]
+    fn test_positional_arguments() {
+        let args = ["lorem", "ipsum"];
+        assert_eq!(exf!("{args[0]} {} {args[1]} {:?}", 1 + 2, "dolor"), r#"lorem 3 ipsum "dolor""#);
+    }
+
+    #[test]
+    fn test_positional_counts() {
+        let x = "lorem";
+        assert_eq!(exf!("{x} {:1$}|{:>1$ x}|", 5, 7), "lorem       5|  lorem|");
+        assert_eq!(exf!("{:.0$ x} {:>1$.0$}|", 2, 4), "lo    2|");
+    }
+
+    #[test]
+    fn test_named_arguments() {
+        let (x, y) = (1, 2);
+        assert_eq!(exf!("{a} + {b} = {}", x + y, a = x, b = y), "1 + 2 = 3");
+    }
+
+    #[test]
+    fn test_named_arguments_with_specs() {
+        let value = 27;
+        assert_eq!(exf!("{:#x value} {:>4 value}|", value = value * 2), "0x36   54|");
+    }
+
+    #[test]
+    fn test_named_argument_in_expression() {
+        let lorem = "lorem";
+        assert_eq!(exf!("{lorem.len()} {lorem}", lorem = "ipsum"), "5 ipsum");
+    }
+
+    #[test]
+    fn test_trailing_generic_arguments() {
+        use std::collections::HashMap;
+
+        let map = HashMap::<u8, u8>::new();
+        assert_eq!(exf!("{} {} {map.len()}", HashMap::<u8, u8>::new().len(), 1), "0 1 0");
+    }
+
+    #[test]
+    fn test_trailing_arguments_in_other_macros() {
+        use crate::short::exw;
+        use std::fmt::Write;
+
+        let mut s = String::new();
+        exw!(s, "{} {value}", ',', value = "lorem").unwrap();
+        assert_eq!(s, ", lorem");
+        assert_eq!(crate::ex_display!("{:?}", [1]).to_string(), "[1]");
+    }
+
+    #[test]
+    fn test_self_documenting() {
+        let args = ["lorem", "ipsum"];
+        assert_eq!(exf!("{=args[0]} {= args[1] }"), "args[0]=lorem args[1]=ipsum");
+    }
+
+    #[test]
+    fn test_self_documenting_with_spec() {
+        let value = 27;
+        assert_eq!(exf!("{:#x=value} {:?=Some(value)}"), "value=0x1b Some(value)=Some(27)");
+    }
+
+    #[test]
+    fn test_self_documenting_escapes() {
+        assert_eq!(exf!(r#"{=r"{}"}"#), r#"r"{}"={}"#);
+        assert_eq!(exf!(r#"{:?="lorem"}"#), r#""lorem"="lorem""#);
+    }
+
+    #[test]
+    fn test_dbg_returns_value() {
+        use crate::short::exd;
+
+        let args = vec!["lorem", "ipsum"];
+        let len = exd!("{:?args}: {:#?}", args.len()) + 1;
+        assert_eq!(len, 3);
+    }
+
+    #[test]
+    fn test_dbg_moves_value() {
+        use crate::short::exd;
+
+        let args = vec!["lorem", "ipsum"];
+        let moved = exd!("value = {:?}", args);
+        assert_eq!(moved, ["lorem", "ipsum"]);
+    }
+
+    #[test]
+    fn test_dbg_without_value() {
+        use crate::short::exd;
+
+        let arg = "lorem";
+        exd!("{arg}");
+    }
+
+    #[test]
+    #[should_panic(expected = "lorem ipsum 42")]
+    fn test_panic() {
+        let args = ("ipsum", 42);
+        crate::short::expn!("lorem {args.0} {args.1}");
+    }
+
+    #[test]
+    #[should_panic(expected = "{lorem}")]
+    fn test_panic_escaped_brackets() {
+        crate::short::expn!("{{lorem}}");
+    }
+
+    #[test]
+    fn test_panic_location_with_track_caller() {
+        use std::sync::{Arc, Mutex};
+
+        #[track_caller]
+        fn check(value: i32) {
+            if value < 0 {
+                crate::short::expn!("negative value {value}");
+            }
+        }
+
+        let location = Arc::new(Mutex::new(None));
+        let hook_location = location.clone();
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if info.to_string().contains("negative value -1") {
+                *hook_location.lock().unwrap() = info.location().map(|l| l.line());
+            }
+        }));
+
+        let line = line!() + 1;
+        let result = std::panic::catch_unwind(|| check(-1));
+        std::panic::set_hook(default_hook);
+
+        assert!(result.is_err());
+        assert_eq!(*location.lock().unwrap(), Some(line));
+    }
+
+    #[test]
+    #[should_panic(expected = "internal error: entered unreachable code: state 3")]
+    fn test_unreachable() {
+        let state = 3;
+        crate::short::exur!("state {state}");
+    }
+
+    #[test]
+    #[should_panic(expected = "not yet implemented: lorem")]
+    fn test_todo() {
+        crate::short::extd!(r#"{"lorem"}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "not implemented: ipsum")]
+    fn test_unimplemented() {
+        crate::short::exui!(r#"{"ipsum"}"#);
+    }
+
+    #[test]
+    fn test_assert_without_message() {
+        crate::ex_assert!(1 + 1 == 2);
+        crate::ex_assert_eq!(vec![1, 2], [1, 2]);
+        crate::ex_assert_ne!(1, 2,);
+    }
+
+    #[test]
+    fn test_assert_generic_arguments() {
+        use std::collections::HashMap;
+
+        let map: HashMap<u8, u8> = HashMap::new();
+        let len = 0;
+        crate::ex_assert_eq!(HashMap::<u8, u8>::new(), map, "{:?map}");
+        crate::ex_assert!(<HashMap<u8, Vec<u8>> as Default>::default().is_empty(), "{len}");
+        crate::ex_assert!(len < 1, "{len} >= 1");
+    }
+
+    #[test]
+    #[should_panic(expected = "lorem has 5 chars")]
+    fn test_assert_message() {
+        let arg = "lorem";
+        crate::ex_assert!(arg.is_empty(), "{arg} has {arg.len()} chars");
+    }
+
+    #[test]
+    #[should_panic(expected = "failed: lorem ipsum\n  left: 1\n right: 2")]
+    fn test_assert_eq_message() {
+        let args = ["lorem", "ipsum"];
+        crate::ex_assert_eq!(1, 2, "{args[0]} {args[1]}");
+    }
+
+    #[test]
+    #[should_panic(expected = "failed: 1 items\n  left: [1]\n right: [1]")]
+    fn test_assert_ne_message() {
+        let items = vec![1];
+        crate::ex_assert_ne!(items, [1], "{items.len()} items");
+    }
+
+    #[test]
+    fn test_assert_message_is_lazy() {
+        let mut formatted = 0;
+        let mut count = || {
+            formatted += 1;
+            formatted
+        };
+        crate::ex_assert_eq!(1, 1, "{count()}");
+        crate::ex_debug_assert!(true, "{count()}");
+        crate::ex_debug_assert_eq!(1, 1, "{count()}");
+        crate::ex_debug_assert_ne!(1, 2, "{count()}");
+        assert_eq!(formatted, 0);
+    }
+
+    #[test]
+    fn test_format_args() {
+        use crate::short::exfa;
+
+        let args = ["lorem", "ipsum"];
+        assert_eq!(format!("{}!", exfa!("{args[0]} {:>6 args[1]}")), "lorem  ipsum!");
+    }
+
+    #[test]
+    fn test_display_is_lazy() {
+        use crate::short::exds;
+        use std::cell::Cell;
+
+        let evaluated = Cell::new(0);
+        let count = || {
+            evaluated.set(evaluated.get() + 1);
+            evaluated.get()
+        };
+        let display = exds!("lorem {count()}");
+        assert_eq!(evaluated.get(), 0);
+        assert_eq!(display.to_string(), "lorem 1");
+        assert_eq!(format!("{:?}", display), "lorem 2");
+    }
+
+    #[test]
+    fn test_display_in_format() {
+        use crate::short::exds;
+
+        let arg = 42;
+        let display = exds!("{:04 arg}");
+        assert_eq!(format!("[{}]", display), "[0042]");
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_display_struct() {
+        use crate::ExDisplay;
+        use std::fmt::Display;
+
+        #[derive(ExDisplay)]
+        #[ex_display("{_0}:{:>4 _1}")]
+        struct Pair<T: Display>(&'static str, T);
+
+        #[derive(ExDisplay)]
+        #[ex_display("{{{name}}} {:?=self.values} {}", values.len())]
+        struct Named {
+            name: String,
+            values: Vec<u8>,
+        }
+
+        #[derive(ExDisplay)]
+        #[ex_display("unit")]
+        struct Unit;
+
+        assert_eq!(Pair("a", 1.5).to_string(), "a: 1.5");
+        let named = Named { name: "lorem".to_string(), values: vec![1, 2] };
+        assert_eq!(format!("{:>30}", named), "{lorem} self.values=[1, 2] 2");
+        assert_eq!(Unit.to_string(), "unit");
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_display_enum() {
+        use crate::ExDisplay;
+
+        #[derive(ExDisplay)]
+        enum Message<'a> {
+            #[ex_display("quit")]
+            Quit,
+            #[ex_display("move to ({x}, {y})")]
+            Move { x: i32, y: i32 },
+            #[ex_display("write {:?_0} ({_0.len()} bytes)")]
+            Write(&'a str),
+        }
+
+        assert_eq!(Message::Quit.to_string(), "quit");
+        assert_eq!(Message::Move { x: 1, y: -2 }.to_string(), "move to (1, -2)");
+        assert_eq!(Message::Write("hi").to_string(), r#"write "hi" (2 bytes)"#);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive_error() {
+        use crate::ExError;
+        use std::error::Error;
+        use std::fmt::Debug;
+
+        #[derive(Debug, ExError)]
+        #[ex_error("wrapped: {_0}")]
+        struct Wrapped(#[from] std::fmt::Error);
+
+        #[derive(Debug, ExError)]
+        enum Failure<T: Debug> {
+            #[ex_error("value {:?value} at {index}")]
+            Value { value: T, index: usize },
+            #[ex_error("boxed {}", source.to_string().len())]
+            Boxed { source: Box<dyn Error + Send + Sync> },
+            #[ex_error("{_1}")]
+            Wrapped(u8, #[source] Wrapped),
+        }
+
+        let wrapped = Wrapped::from(std::fmt::Error);
+        assert_eq!(wrapped.to_string(), "wrapped: an error occurred when formatting an argument");
+        assert!(wrapped.source().unwrap().is::<std::fmt::Error>());
+
+        let value = Failure::Value { value: "x", index: 2 };
+        assert_eq!(value.to_string(), r#"value "x" at 2"#);
+        assert!(value.source().is_none());
+
+        let boxed = Failure::<()>::Boxed { source: "lorem".into() };
+        assert_eq!(boxed.to_string(), "boxed 5");
+        assert_eq!(boxed.source().unwrap().to_string(), "lorem");
+
+        let nested = Failure::<()>::Wrapped(1, wrapped);
+        assert!(nested.source().unwrap().source().unwrap().is::<std::fmt::Error>());
+    }
+
+    #[test]
+    fn test_html_escape() {
+        use crate::short::exh;
+
+        let text = r#"<a href="x">Tom & 'Jerry'</a>"#;
+        assert_eq!(
+            exh!("<p>{text}</p>").as_str(),
+            "<p>&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;</p>"
+        );
+    }
+
+    #[test]
+    fn test_html_raw() {
+        use crate::short::exh;
+
+        let bold = "<b>lorem</b>";
+        let fragment = exh!("<i>{bold}</i>");
+        assert_eq!(
+            exh!("{!bold} {!fragment}").into_string(),
+            "<b>lorem</b> <i>&lt;b&gt;lorem&lt;/b&gt;</i>"
+        );
+    }
+
+    #[test]
+    fn test_html_safe() {
+        use crate::short::exh;
+
+        let item = exh!("<li>{'&'}</li>");
+        let items = [exh!("<li>{'<'}</li>")];
+        assert_eq!(
+            exh!("<ul>{item}{&item}{items[0]}{:>16 item}</ul>").as_str(),
+            "<ul><li>&amp;</li><li>&amp;</li><li>&lt;</li>  <li>&amp;</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_html_safe_wrapped() {
+        use crate::short::exh;
+        use std::rc::Rc;
+
+        let boxed = Box::new(exh!("<b>{'&'}</b>"));
+        let shared = Rc::new(exh!("<i>{'<'}</i>"));
+        let wrapped = Some(exh!("<p></p>"));
+        assert_eq!(
+            exh!("{boxed}{shared}{wrapped.as_ref().unwrap()}").as_str(),
+            "<b>&amp;</b><i>&lt;</i><p></p>"
+        );
+    }
+
+    #[test]
+    fn test_html_spec() {
+        use crate::short::exh;
+
+        let width = 4;
+        let values = ["<", "&"];
+        assert_eq!(
+            exh!("{:>width$ values[0]}|{:?=values[1]}|{:-^*{width + 1} values[1]}").as_str(),
+            r#"   &lt;|values[1]=&quot;&amp;&quot;|--&amp;--"#
+        );
+    }
+
+    #[test]
+    fn test_url() {
+        use crate::ex_url;
+
+        let segment = "ä/?#%";
+        let key = "a=b&c+d";
+        let value = "=&#é ";
+        assert_eq!(
+            ex_url!("https://{!"example.com"}/{segment};{segment}?{key}={value}&{key}#{segment}&{key}"),
+            concat!(
+                "https://example.com/%C3%A4%2F%3F%23%25;%C3%A4%2F%3F%23%25",
+                "?a%3Db%26c%2Bd==%26%23%C3%A9%20&a%3Db%26c%2Bd",
+                "#%C3%A4/?%23%25&a=b&c+d"
+            )
+        );
+    }
+
+    #[test]
+    fn test_url_raw_string() {
+        use crate::ex_url;
+
+        let id = "a/b&c";
+        assert_eq!(ex_url!(r#"https://x.com/{id}?q={id}#{id}"#), "https://x.com/a%2Fb&c?q=a/b%26c#a/b&c");
+        assert_eq!(ex_url!(r##"/{id}?{id}={id}"##), ex_url!("/{id}?{id}={id}"));
+    }
+
+    #[test]
+    fn test_url_spec() {
+        use crate::ex_url;
+
+        let page = 7;
+        let name = "a b";
+        assert_eq!(ex_url!("/p/{:03 page}?n={:?name}"), "/p/007?n=%22a%20b%22");
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn test_url_parse() {
+        use crate::ex_url_parse;
+
+        let id = "1/2";
+        let url = ex_url_parse!("https://example.com/items/{id}?next={id}").unwrap();
+        assert_eq!(url.path_segments().unwrap().collect::<Vec<_>>(), ["items", "1%2F2"]);
+        assert_eq!(url.query_pairs().next().unwrap().1, "1/2");
+        assert!(ex_url_parse!("{id}").is_err());
+    }
+
+    #[test]
+    fn test_scan() {
+        use crate::ex_scan;
+
+        let mut values = [0u8; 3];
+        let mut flag = false;
+        let mut rest = String::new();
+        let input = String::from("a=0x1F b=  7|c=101 true: {x}");
+        let result = ex_scan!(&input, "a={:#x values[0]} b={:>3 values[1]}|c={:b values[2]} {flag}: {{{rest}}}");
+        assert_eq!(result, Ok(()));
+        assert_eq!((values, flag, rest.as_str()), ([31, 7, 5], true, "x"));
+    }
+
+    #[test]
+    fn test_scan_skip() {
+        use crate::ex_scan;
+
+        let (mut year, mut day) = (0, 0);
+        assert_eq!(ex_scan!("2024-03-09", "{:4 year}-{:2}-{day}"), Ok(()));
+        assert_eq!((year, day), (2024, 9));
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        use crate::ex_scan;
+        use crate::scan::ScanError;
+
+        let (mut x, mut y) = (1i32, 2i32);
+        assert_eq!(ex_scan!("5 6", "{x}-{y}"), Err(ScanError::Mismatch { expected: "-", position: 3 }));
+        assert_eq!(ex_scan!("(5-6", "({x}-{y})"), Err(ScanError::Mismatch { expected: ")", position: 4 }));
+        assert_eq!(ex_scan!("5-6 ", "{x}-{:1 y}"), Err(ScanError::Trailing { position: 3 }));
+        assert_eq!(
+            ex_scan!("5-zz", "{x}-{:x y}").unwrap_err().to_string(),
+            r#"invalid value "zz" for `{:x y}` at byte 2: invalid digit found in string"#
+        );
+        assert_eq!(
+            ex_scan!("5-1f", "{x}-{:#x y}"),
+            Err(ScanError::Invalid {
+                placeholder: "{:#x y}",
+                position: 2,
+                text: "1f".to_string(),
+                message: "missing prefix `0x`".to_string()
+            })
+        );
+        assert_eq!(
+            ex_scan!("5-zz", "{x}-{y => :x}").unwrap_err().to_string(),
+            r#"invalid value "zz" for `{y => :x}` at byte 2: invalid digit found in string"#
+        );
+        assert_eq!((x, y), (1, 2));
+    }
+
+    #[test]
+    fn test_scan_upper_hex() {
+        use crate::ex_scan;
+
+        let (mut x, mut y) = (0u32, 0u32);
+        assert_eq!(ex_scan!("0X1F 0xff", "{:#X x} {:#X y}"), Ok(()));
+        assert_eq!((x, y), (31, 255));
+        assert_eq!(ex_scan!(&format!("{:#X}", 0xabc), "{:#X x}"), Ok(()));
+        assert_eq!(x, 0xabc);
+    }
+
+    #[test]
+    fn test_sql() {
+        use crate::ex_sql;
+
+        let ids = [3, 4];
+        let x = "{x}";
+        let query = ex_sql!("DELETE FROM t WHERE id IN ({ids[0]}, {ids[1] + 1}) AND '{{x}}' = {x}");
+        assert_eq!(query.sql, "DELETE FROM t WHERE id IN (?, ?) AND '{x}' = ?");
+        assert_eq!(query.params, (&3, &5, &"{x}"));
+        assert_eq!(ex_sql!(numbered, "SELECT 1").params, ());
+    }
+
+    #[test]
+    fn test_cmd() {
+        use crate::ex_cmd;
+        use std::ffi::OsStr;
+        use std::path::PathBuf;
+
+        let message = "lorem 'ipsum' && dolor";
+        let dir = PathBuf::from("/tmp/sit amet");
+        let width = 4;
+        let cmd = ex_cmd!("{"git"} -C {dir} commit --message={message} -n{:0>width$ 7} {:?=width}");
+        let args: Vec<&OsStr> = cmd.get_args().collect();
+        assert_eq!(cmd.get_program(), "git");
+        assert_eq!(
+            args,
+            ["-C", "/tmp/sit amet", "commit", "--message=lorem 'ipsum' && dolor", "-n0007", "width=4"]
+        );
+    }
+
+    #[test]
+    fn test_cmd_spread() {
+        use crate::ex_cmd;
+
+        let files = vec!["a b".to_string(), "c".to_string()];
+        let numbers = [1, 2];
+        let cmd = ex_cmd!("ls  {..&files}\t{..numbers.iter()} {..Vec::<String>::new()}");
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, ["a b", "c", "1", "2"]);
+    }
+
+    #[cfg(feature = "rusqlite")]
+    #[test]
+    fn test_sql_rusqlite() {
+        use crate::ex_sql;
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        ex_sql!("CREATE TABLE users (id INTEGER, name TEXT)").execute(&conn).unwrap();
+
+        let users = [(1, "lorem"), (2, "ipsum'); DROP TABLE users;--")];
+        for (id, name) in users.iter() {
+            let inserted = ex_sql!(numbered, "INSERT INTO users VALUES ({id}, {name})").execute(&conn);
+            assert_eq!(inserted, Ok(1));
+        }
+
+        let id = 2;
+        let name: String = ex_sql!(named, "SELECT name FROM users WHERE id = {id} OR id = {id} + 10")
+            .query_row(&conn, |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, users[1].1);
+
+        let count: i64 = ex_sql!("SELECT COUNT(*) FROM users").query_row(&conn, |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_try_print() -> std::io::Result<()> {
+        use crate::{try_ex_eprint, try_ex_eprintdoc, try_ex_eprintln, try_ex_print, try_ex_printdoc, try_ex_println};
+
+        let value = 1;
+        try_ex_print!("{value} ")?;
+        try_ex_println!("{:>3 value}")?;
+        try_ex_eprint!("{value} ")?;
+        try_ex_eprintln!("{:?=value}")?;
+        try_ex_printdoc!("
+            {value}
+        ")?;
+        try_ex_eprintdoc!("
+            {value} {}
+        ", value + 1)
+    }
+
+    #[test]
+    fn test_try_print_methods() -> std::io::Result<()> {
+        use crate::try_ex_println;
+        use std::fmt::Write as _;
+
+        // `write_fmt` of `fmt::Write` stays unambiguous in the expressions
+        struct Both(Vec<u8>);
+        impl std::fmt::Write for Both {
+            fn write_str(&mut self, s: &str) -> std::fmt::Result {
+                self.0.extend(s.bytes());
+                Ok(())
+            }
+        }
+        impl std::io::Write for Both {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.extend(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut both = Both(Vec::new());
+        try_ex_println!(r#"{both.write_fmt(format_args!("lorem")).is_ok()}"#)?;
+        assert_eq!(both.0, b"lorem");
+        Ok(())
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_anyhow() {
+        use crate::{ex_anyhow, ex_bail, ex_ensure};
+
+        fn check(value: i32) -> anyhow::Result<i32> {
+            ex_ensure!(value >= 0, "{value} is negative");
+            ex_ensure!(value != 1);
+            if value > 9 {
+                ex_bail!("{value} has {:>3 value.to_string().len()} digits");
+            }
+            Err(ex_anyhow!("{{{value}}}"))
+        }
+
+        assert_eq!(check(-1).unwrap_err().to_string(), "-1 is negative");
+        assert_eq!(check(1).unwrap_err().to_string(), "Condition failed: `value != 1` (1 vs 1)");
+        assert_eq!(check(10).unwrap_err().to_string(), "10 has   2 digits");
+        assert_eq!(check(2).unwrap_err().to_string(), "{2}");
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_with_context_is_lazy() {
+        use crate::ex_with_context;
+        use std::cell::Cell;
+
+        let formatted = Cell::new(0);
+        let count = || {
+            formatted.set(formatted.get() + 1);
+            formatted.get()
+        };
+
+        let ok: Result<i32, std::fmt::Error> = Ok(1);
+        assert_eq!(ex_with_context!(ok, "lorem {count()}").unwrap(), 1);
+        assert_eq!(formatted.get(), 0);
+
+        let error = ex_with_context!(None::<i32>, "lorem {count()}").unwrap_err();
+        assert_eq!(error.to_string(), "lorem 1");
+
+        let failed: anyhow::Result<()> = Err(error);
+        let error = ex_with_context!(failed, "ipsum {count()}").unwrap_err();
+        assert_eq!(format!("{:#}", error), "ipsum 2: lorem 1");
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_template() {
+        use crate::runtime::Template;
+        use serde_json::json;
+
+        let context = json!({
+            "name": "lorem",
+            "items": ["ipsum", "dolor"],
+            "index": 1,
+            "map": { "sit amet": 2.5, "7": true },
+            "point": [3, -4],
+            "width": 6,
+        });
+
+        let render = |template: &str| Template::parse(template).unwrap().render(&context).unwrap();
+        assert_eq!(render("{name} {items[0]} {items[index]}"), "lorem ipsum dolor");
+        assert_eq!(render(r#"{map["sit amet"]} {map[7]} {point.1}"#), "2.5 true -4");
+        assert_eq!(render("{{{=name}}} {:?name} {:?items}"), r#"{name=lorem} "lorem" ["ipsum","dolor"]"#);
+        assert_eq!(render("{:-<7 name}|{:>width$ index}|{:^*width.*index map[\"sit amet\"]}|"), "lorem--|     1| 2.5  |");
+        assert_eq!(render("{:#06x point[0]} {:+ index} {:05 point.1} {:b index} {:.2 name} {:e width}"), "0x0003 +1 -0004 1 lo 6e0");
+        assert_eq!(render("{name => :>7}|{=items[index] => :?}"), r#"  lorem|items[index]="dolor""#);
+
+        let context = json!({ "half": 0.5, "zero": 0, "yes": true });
+        let render = |template: &str| Template::parse(template).unwrap().render(&context).unwrap();
+        assert_eq!(render("{:#08? half}|{:#010e zero}|{:#010E half}"), format!("{:#08?}|{:#010e}|{:#010E}", 0.5, 0, 0.5));
+        assert_eq!(render("{:#06x zero}|{:.2 yes}|{:>5.3 yes}|"), format!("{:#06x}|{:.2}|{:>5.3}|", 0, true, true));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_template_serialize() {
+        use crate::runtime::Template;
+        use std::collections::HashMap;
+
+        #[derive(serde::Serialize)]
+        struct User {
+            name: &'static str,
+            tags: Vec<&'static str>,
+        }
+
+        let template: Template = "{user.name} ({user.tags[1]})".parse().unwrap();
+        let mut context = HashMap::new();
+        context.insert("user", User { name: "lorem", tags: vec!["ipsum", "dolor"] });
+        assert_eq!(template.render(&context).unwrap(), "lorem (dolor)");
+        assert_eq!(template.source(), "{user.name} ({user.tags[1]})");
+    }
+
+    #[cfg(feature = "runtime")]
+    #[test]
+    fn test_runtime_template_errors() {
+        use crate::runtime::{ParseError, RenderError, Template};
+        use serde_json::json;
+
+        let parse = |template: &str| Template::parse(template).unwrap_err();
+        let error = |offset, message: &str| ParseError { offset, message: message.to_string() };
+        assert_eq!(parse("lorem {ipsum"), error(6, "unclosed `{`"));
+        assert_eq!(parse("lorem } ipsum"), error(6, "unmatched `}`"));
+        assert_eq!(parse("lorem {}"), error(7, "expected an expression"));
+        assert_eq!(parse("lorem {ipsum.len()}"), error(16, "expected a path like `name`, `a.b` or `a[0]`"));
+        assert_eq!(parse("lorem {:1$ ipsum}"), error(7, "positional arguments aren't supported"));
+        assert_eq!(parse("lorem {ipsum[0}"), error(14, "expected `]`"));
+
+        let context = json!({ "ipsum": [1, 2], "dolor": "Sit" });
+        let render = |template: &str| Template::parse(template).unwrap().render(&context).unwrap_err();
+        let error = |offset, message: &str| RenderError { offset, message: message.to_string() };
+        assert_eq!(render("lorem {amet}"), error(7, "`amet` is not in the context"));
+        assert_eq!(render("lorem {ipsum[2]}"), error(7, "`ipsum[2]` is not in the context"));
+        assert_eq!(render("lorem {:x dolor}"), error(10, r#""Sit" can't be formatted as lowerhex"#));
+        assert_eq!(render("lorem {:dolor$ ipsum}"), error(8, "`dolor` is not a width or precision"));
+        assert_eq!(render("lorem {:x dolor}").to_string(), r#""Sit" can't be formatted as lowerhex at byte 10"#);
+    }
+
+    #[cfg(feature = "log")]
+    mod logger {
+        use std::sync::{Mutex, Once};
+
+        static RECORDS: Mutex<Vec<(log::Level, String, String)>> = Mutex::new(Vec::new());
+
+        struct Logger;
+
+        impl log::Log for Logger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                RECORDS.lock().unwrap().push((
+                    record.level(),
+                    record.target().to_string(),
+                    record.args().to_string(),
+                ));
+            }
+
+            fn flush(&self) {}
+        }
+
+        // logs up to the info level and returns the records containing `filter`
+        pub fn records(filter: &str) -> Vec<(log::Level, String, String)> {
+            static INIT: Once = Once::new();
+            INIT.call_once(|| {
+                log::set_logger(&Logger).unwrap();
+                log::set_max_level(log::LevelFilter::Info);
+            });
+
+            RECORDS
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, _, message)| message.contains(filter))
+                .cloned()
+                .collect()
+        }
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_levels() {
+        logger::records("");
+        let arg = "levels";
+        crate::ex_error!("lorem {arg}");
+        crate::ex_warn!("ipsum {arg}");
+        crate::ex_info!(target: "dolor", "dolor {arg}");
+        crate::ex_log!(log::Level::Warn, "sit {arg}");
+        crate::ex_log!(target: "amet", log::Level::Error, "amet {arg}");
+
+        assert_eq!(
+            logger::records("levels"),
+            [
+                (log::Level::Error, module_path!().to_string(), "lorem levels".to_string()),
+                (log::Level::Warn, module_path!().to_string(), "ipsum levels".to_string()),
+                (log::Level::Info, "dolor".to_string(), "dolor levels".to_string()),
+                (log::Level::Warn, module_path!().to_string(), "sit levels".to_string()),
+                (log::Level::Error, "amet".to_string(), "amet levels".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_disabled_level_is_lazy() {
+        use std::cell::Cell;
+
+        logger::records("");
+        let evaluated = Cell::new(false);
+        let evaluate = || {
+            evaluated.set(true);
+            "lazy"
+        };
+        crate::ex_debug!("{evaluate()}");
+        crate::ex_trace!(target: "lorem", "{evaluate()}");
+
+        assert!(!evaluated.get());
+        assert!(logger::records("lazy").is_empty());
+    }
+
+    #[cfg(feature = "tracing")]
+    mod subscriber {
+        use std::fmt;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        // target and fields of each event
+        pub type Events = Arc<Mutex<Vec<(String, Vec<(String, String)>)>>>;
+
+        #[derive(Default)]
+        pub struct Subscriber {
+            pub events: Events,
+            pub max_level: Option<tracing::Level>,
+        }
+
+        struct Fields(Vec<(String, String)>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.push((field.name().to_string(), format!("{:?}", value)));
+            }
+        }
+
+        impl tracing::Subscriber for Subscriber {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                match self.max_level {
+                    Some(level) => *metadata.level() <= level,
+                    None => true,
+                }
+            }
+
+            fn new_span(&self, _: &Attributes) -> Id {
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event) {
+                let mut fields = Fields(Vec::new());
+                event.record(&mut fields);
+                let target = event.metadata().target().to_string();
+                self.events.lock().unwrap().push((target, fields.0));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_event_fields() {
+        use tracing::Level;
+
+        struct User {
+            id: u32,
+        }
+
+        let subscriber = subscriber::Subscriber::default();
+        let events = subscriber.events.clone();
+        let user = User { id: 7 };
+        let items = ["lorem", "ipsum"];
+
+        tracing::subscriber::with_default(subscriber, || {
+            crate::ex_event!(Level::INFO, "user {user.id} bought {count = items.len()} {:?items}");
+            crate::ex_event!(target: "dolor", Level::WARN, "{:>4 user.id}|{user.id}");
+        });
+
+        let field = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (
+                    module_path!().to_string(),
+                    vec![
+                        field("message", r#"user 7 bought 2 ["lorem", "ipsum"]"#),
+                        field("user.id", "7"),
+                        field("count", "2"),
+                        field("items", r#"["lorem", "ipsum"]"#),
+                    ]
+                ),
+                (
+                    "dolor".to_string(),
+                    vec![field("message", "   7|7"), field("user.id", "7")]
+                ),
+            ]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_event_disabled_level_is_lazy() {
+        use std::cell::Cell;
+        use tracing::Level;
+
+        let subscriber = subscriber::Subscriber {
+            max_level: Some(Level::INFO),
+            ..Default::default()
+        };
+        let events = subscriber.events.clone();
+        let evaluated = Cell::new(0);
+        let evaluate = || evaluated.set(evaluated.get() + 1);
+
+        tracing::subscriber::with_default(subscriber, || {
+            crate::ex_event!(Level::DEBUG, "{:?value = evaluate()}");
+            crate::ex_event!(Level::INFO, "{:?value = evaluate()}");
+        });
+
+        assert_eq!(evaluated.get(), 1);
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_formatdoc() {
+        let args = ["lorem", "ipsum"];
+        assert_eq!(
+            crate::ex_formatdoc!(
+                "
+                {args[0]}:
+                    {args[1]}
+
+                  {args.len()}
+                "
+            ),
+            "lorem:\n    ipsum\n\n  2\n"
+        );
+    }
+
+    #[test]
+    fn test_formatdoc_keeps_escaped_line_breaks() {
+        let value = 1;
+        assert_eq!(crate::ex_formatdoc!("lorem\n    {value}"), "lorem\n    1");
+    }
+
+    #[test]
+    fn test_formatdoc_keeps_value_indentation() {
+        let value = "lorem\n    ipsum";
+        assert_eq!(
+            crate::ex_formatdoc!(
+                r#"
+                    {value}
+                      {"dolor"}"#
+            ),
+            "lorem\n    ipsum\n  dolor"
+        );
+    }
+
+    #[test]
+    fn test_formatdoc_with_block_expression() {
+        assert_eq!(
+            crate::ex_formatdoc!(
+                "
+                lorem { {
+                        let arg = 1;
+                        arg + 1
+                    }}
+                  ipsum {}
+                ",
+                3
+            ),
+            "lorem 2\n  ipsum 3\n"
+        );
+    }
+
+    #[test]
+    fn test_writedoc() {
+        use std::fmt::Write;
+
+        let mut s = String::new();
+        let arg = "lorem";
+        crate::ex_writedoc!(
+            s,
+            "
+            {arg}
+              {arg}"
+        )
+        .unwrap();
+        assert_eq!(s, "lorem\n  lorem");
+    }
+
+    #[test]
+    fn test_write_fmt() {
+        use crate::short::exw;
+        use std::fmt::Write;
+
+        let mut s = String::new();
+        let args = ["lorem", "ipsum"];
+        exw!(s, "{args[0]} {:>6 args[1]}").unwrap();
+        assert_eq!(s, "lorem  ipsum");
+    }
+
+    #[test]
+    fn test_writeln_io() {
+        use crate::short::exwl;
+        use std::io::Write;
+
+        let mut out = Vec::new();
+        let args = vec![1, 2];
+        exwl!(out, "{:?args}").unwrap();
+        exwl!(&mut out, "{args.len()}").unwrap();
+        assert_eq!(out, b"[1, 2]\n2\n");
+    }
+
     #[test]
     fn test_format_alignment_with_char() {
         assert_eq!(exf!(r#"{:'>10 "test"}"#), "''''''test");
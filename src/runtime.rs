@@ -0,0 +1,547 @@
+//! Templates parsed at runtime with the syntax of [`ex_format!`](../macro.ex_format.html).
+//!
+//! Expressions are limited to paths into the context: names, fields and indexing, like
+//! `user.name`, `items[0]`, `point.0`, `map["key"]` or `items[index]`. Format specs, `$` and `*`
+//! widths and precisions, `{=expr}` and the `{{` and `}}` escapes work as in `ex_format!`.
+//! Requires the `runtime` feature.
+//!
+//! # Example
+//! ```
+//! use expression_format::runtime::Template;
+//! use serde_json::json;
+//!
+//! let template = Template::parse("Hello {user.name}, you have {:>3 count} msgs").unwrap();
+//! let context = json!({ "user": { "name": "Jane" }, "count": 7 });
+//! assert_eq!(template.render(&context).unwrap(), "Hello Jane, you have   7 msgs");
+//!
+//! let error = Template::parse("Hello {user.name").unwrap_err();
+//! assert_eq!(error.to_string(), "unclosed `{` at byte 6");
+//! ```
+
+use crate::syntax::{self, Align, Segment, Sign};
+use serde::Serialize;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Error returned when a template can't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset in the template.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Error returned when a template can't be rendered with a context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderError {
+    /// Byte offset in the template of the expression that failed.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl Error for RenderError {}
+
+/// A template parsed at runtime.
+///
+/// See the [module documentation](index.html) for the supported expressions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    /// Parses a template.
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+
+        for segment in syntax::parse(source) {
+            let placeholder = match segment {
+                Segment::Text(span) => {
+                    unescape_text(source, span, &mut text)?;
+                    continue;
+                }
+                Segment::Placeholder(placeholder) => placeholder,
+            };
+
+            if placeholder.self_doc {
+                text.push_str(source[placeholder.expr.clone()].trim());
+                text.push('=');
+            }
+
+            let spec = match &placeholder.spec {
+                Some(spec) => convert_spec(source, spec)?,
+                None => Spec::default(),
+            };
+            let path = parse_expr(source, placeholder.expr)?;
+
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+            pieces.push(Piece::Value(Box::new(Embedded { spec, path })));
+        }
+
+        pieces.push(Piece::Text(text));
+        Ok(Template { source: source.to_string(), pieces })
+    }
+
+    /// Returns the template as it was parsed.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Renders the template with a context, like a map or a struct that implements `Serialize`.
+    pub fn render<C: Serialize + ?Sized>(&self, context: &C) -> Result<String, RenderError> {
+        let context = serde_json::to_value(context).map_err(|error| render_error(0, error))?;
+        self.render_value(&context)
+    }
+
+    /// Renders the template with a context that is already a JSON value.
+    pub fn render_value(&self, context: &Value) -> Result<String, RenderError> {
+        let mut result = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => result.push_str(text),
+                Piece::Value(value) => {
+                    let width = value.spec.width.as_ref().map(|count| count.eval(context)).transpose()?;
+                    let precision = value.spec.precision.as_ref().map(|count| count.eval(context)).transpose()?;
+                    let found = value.path.eval(context)?;
+                    format_value(found, &value.spec, width, precision, &mut result)
+                        .map_err(|message| render_error(value.path.offset, message))?;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl FromStr for Template {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, ParseError> {
+        Template::parse(source)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    // text with the escapes already resolved
+    Text(String),
+    Value(Box<Embedded>),
+}
+
+// an embedded expression with its spec
+#[derive(Clone, Debug, PartialEq)]
+struct Embedded {
+    spec: Spec,
+    path: Path,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Display,
+    Debug,
+    LowerHex,
+    UpperHex,
+    Octal,
+    Binary,
+    LowerExp,
+    UpperExp,
+}
+
+impl Kind {
+    // the name used in errors
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Display => "display",
+            Kind::Debug => "debug",
+            Kind::LowerHex => "lowerhex",
+            Kind::UpperHex => "upperhex",
+            Kind::Octal => "octal",
+            Kind::Binary => "binary",
+            Kind::LowerExp => "lowerexp",
+            Kind::UpperExp => "upperexp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Count {
+    Literal(usize),
+    Path(Path),
+}
+
+impl Count {
+    fn eval(&self, context: &Value) -> Result<usize, RenderError> {
+        match self {
+            Count::Literal(count) => Ok(*count),
+            Count::Path(path) => match path.eval(context)?.as_u64() {
+                Some(count) => Ok(count as usize),
+                None => Err(render_error(path.offset, format!("`{}` is not a width or precision", path.text))),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    plus: bool,
+    alternate: bool,
+    zero: bool,
+    width: Option<Count>,
+    precision: Option<Count>,
+    kind: Kind,
+}
+
+impl Default for Spec {
+    fn default() -> Self {
+        Spec {
+            fill: ' ',
+            align: None,
+            plus: false,
+            alternate: false,
+            zero: false,
+            width: None,
+            precision: None,
+            kind: Kind::Display,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    // indexing with the value of another path
+    Lookup(Path),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Path {
+    // offset of the path in the template
+    offset: usize,
+    text: String,
+    root: String,
+    steps: Vec<Step>,
+}
+
+impl Path {
+    fn eval<'a>(&self, context: &'a Value) -> Result<&'a Value, RenderError> {
+        let error = |message: String| render_error(self.offset, message);
+        let mut value = context
+            .get(&self.root)
+            .ok_or_else(|| error(format!("`{}` is not in the context", self.root)))?;
+
+        for step in &self.steps {
+            let key = match step {
+                Step::Lookup(path) => match path.eval(context)? {
+                    Value::String(key) => Step::Key(key.clone()),
+                    Value::Number(index) if index.is_u64() => Step::Index(index.as_u64().unwrap() as usize),
+                    _ => return Err(error(format!("`{}` is not a key or an index", path.text))),
+                },
+                step => step.clone(),
+            };
+
+            value = match (key, value) {
+                (Step::Key(key), Value::Object(map)) => map.get(&key),
+                (Step::Index(index), Value::Array(items)) => items.get(index),
+                (Step::Index(index), Value::Object(map)) => map.get(&index.to_string()),
+                _ => None,
+            }
+            .ok_or_else(|| error(format!("`{}` is not in the context", self.text)))?;
+        }
+
+        Ok(value)
+    }
+}
+
+fn parse_error(offset: usize, message: impl Display) -> ParseError {
+    ParseError { offset, message: message.to_string() }
+}
+
+fn render_error(offset: usize, message: impl Display) -> RenderError {
+    RenderError { offset, message: message.to_string() }
+}
+
+// `{{` and `}}` are escapes, other brackets are left in the text when they aren't placeholders
+fn unescape_text(source: &str, span: Range<usize>, text: &mut String) -> Result<(), ParseError> {
+    let mut chars = source[span.clone()].char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                chars.next();
+            }
+            '{' => return Err(parse_error(span.start + i, "unclosed `{`")),
+            '}' => return Err(parse_error(span.start + i, "unmatched `}`")),
+            _ => {}
+        }
+        text.push(c);
+    }
+
+    Ok(())
+}
+
+fn convert_spec(source: &str, spec: &syntax::Spec) -> Result<Spec, ParseError> {
+    let kind = match (spec.kind, spec.debug) {
+        (None, false) => Kind::Display,
+        (None, true) => Kind::Debug,
+        (Some('x'), _) => Kind::LowerHex,
+        (Some('X'), _) => Kind::UpperHex,
+        (Some('o'), _) => Kind::Octal,
+        (Some('b'), _) => Kind::Binary,
+        (Some('e'), _) => Kind::LowerExp,
+        (Some('E'), _) => Kind::UpperExp,
+        (Some(_), _) => return Err(parse_error(spec.span.start, "pointers can't be formatted")),
+    };
+
+    let count = |count: &Option<syntax::Count>| match count {
+        None => Ok(None),
+        Some(syntax::Count::Literal(value)) => Ok(Some(Count::Literal(*value))),
+        Some(syntax::Count::Positional(_)) => {
+            Err(parse_error(spec.span.start, "positional arguments aren't supported"))
+        }
+        Some(syntax::Count::Named(span)) | Some(syntax::Count::Expr(span)) => {
+            parse_expr(source, span.clone()).map(|path| Some(Count::Path(path)))
+        }
+    };
+
+    Ok(Spec {
+        fill: spec.fill.unwrap_or(' '),
+        align: spec.align,
+        plus: spec.sign == Some(Sign::Plus),
+        alternate: spec.alternate,
+        zero: spec.zero,
+        width: count(&spec.width)?,
+        precision: count(&spec.precision)?,
+        kind,
+    })
+}
+
+// the whole `source[span]` as a path
+fn parse_expr(source: &str, span: Range<usize>) -> Result<Path, ParseError> {
+    let mut cursor = Cursor { source, index: span.start, end: span.end };
+    cursor.skip_whitespace();
+    if cursor.index == span.end {
+        return Err(parse_error(span.start, "expected an expression"));
+    }
+
+    let path = parse_path(&mut cursor)?;
+    cursor.skip_whitespace();
+    if cursor.index != span.end {
+        return Err(parse_error(cursor.index, "expected a path like `name`, `a.b` or `a[0]`"));
+    }
+
+    Ok(path)
+}
+
+// `name` followed by `.field`, `.0`, `[0]`, `["key"]` or `[path]`
+fn parse_path(cursor: &mut Cursor) -> Result<Path, ParseError> {
+    let offset = cursor.index;
+    let root = parse_ident(cursor)?;
+    let mut steps = Vec::new();
+
+    loop {
+        if cursor.eat('.') {
+            let digits = cursor.take_while(|c| c.is_ascii_digit());
+            if digits.is_empty() {
+                steps.push(Step::Key(parse_ident(cursor)?));
+            } else {
+                steps.push(Step::Index(digits.parse().map_err(|error| parse_error(cursor.index, error))?));
+            }
+        } else if cursor.eat('[') {
+            cursor.skip_whitespace();
+            let index = cursor.index;
+            let digits = cursor.take_while(|c| c.is_ascii_digit());
+            if !digits.is_empty() {
+                steps.push(Step::Index(digits.parse().map_err(|error| parse_error(index, error))?));
+            } else if cursor.peek() == Some('"') {
+                steps.push(Step::Key(parse_string(cursor)?));
+            } else {
+                steps.push(Step::Lookup(parse_path(cursor)?));
+            }
+
+            cursor.skip_whitespace();
+            if !cursor.eat(']') {
+                return Err(parse_error(cursor.index, "expected `]`"));
+            }
+        } else {
+            break;
+        }
+    }
+
+    let text = cursor.source[offset..cursor.index].to_string();
+    Ok(Path { offset, text, root, steps })
+}
+
+fn parse_ident(cursor: &mut Cursor) -> Result<String, ParseError> {
+    let index = cursor.index;
+    match cursor.peek() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return Err(parse_error(index, "expected a name")),
+    }
+
+    Ok(cursor.take_while(|c| c.is_alphanumeric() || c == '_').to_string())
+}
+
+// a string literal with `\"` and `\\` escapes
+fn parse_string(cursor: &mut Cursor) -> Result<String, ParseError> {
+    let start = cursor.index;
+    cursor.index += 1;
+    let mut result = String::new();
+
+    while let Some(c) = cursor.peek() {
+        cursor.index += c.len_utf8();
+        match c {
+            '"' => return Ok(result),
+            '\\' => match cursor.peek() {
+                Some(c @ '"') | Some(c @ '\\') => {
+                    cursor.index += 1;
+                    result.push(c);
+                }
+                _ => return Err(parse_error(cursor.index - 1, "only `\\\"` and `\\\\` can be escaped")),
+            },
+            c => result.push(c),
+        }
+    }
+
+    Err(parse_error(start, "unclosed string"))
+}
+
+struct Cursor<'a> {
+    source: &'a str,
+    index: usize,
+    end: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.index..self.end].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.index += c.len_utf8();
+        }
+        found
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.source[self.index..self.end];
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.index += len;
+        &rest[..len]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+}
+
+// formats `value` like `format!` would format the equivalent rust value with `spec`
+fn format_value(
+    value: &Value,
+    spec: &Spec,
+    width: Option<usize>,
+    precision: Option<usize>,
+    result: &mut String,
+) -> Result<(), String> {
+    let numeric = value.is_number();
+    let integer = value.as_i64().map(i128::from).or_else(|| value.as_u64().map(i128::from));
+    let float = value.as_f64();
+
+    let body = match (spec.kind, value) {
+        (Kind::Display, Value::String(text)) => match precision {
+            Some(precision) => text.chars().take(precision).collect(),
+            None => text.clone(),
+        },
+        (Kind::Display, _) | (Kind::Debug, _) if numeric => match (integer, precision) {
+            (Some(integer), _) => integer.to_string(),
+            (None, Some(precision)) => format!("{:.*}", precision, float.unwrap()),
+            (None, None) if spec.kind == Kind::Debug => format!("{:?}", float.unwrap()),
+            (None, None) => float.unwrap().to_string(),
+        },
+        (Kind::Debug, Value::String(text)) => format!("{:?}", text),
+        (Kind::Display, Value::Bool(_)) | (Kind::Display, Value::Null) => match precision {
+            Some(precision) => value.to_string().chars().take(precision).collect(),
+            None => value.to_string(),
+        },
+        (Kind::Debug, Value::Bool(_)) => value.to_string(),
+        (Kind::Debug, _) if spec.alternate => serde_json::to_string_pretty(value).map_err(|error| error.to_string())?,
+        (Kind::Debug, _) | (Kind::Display, _) => value.to_string(),
+        (Kind::LowerExp, _) | (Kind::UpperExp, _) if numeric => {
+            let text = match precision {
+                Some(precision) => format!("{:.*e}", precision, float.unwrap()),
+                None => format!("{:e}", float.unwrap()),
+            };
+            if spec.kind == Kind::UpperExp {
+                text.to_uppercase()
+            } else {
+                text
+            }
+        }
+        (kind, _) => match integer {
+            // like `format!` for a 64 bit integer, negative values are in two's complement
+            Some(integer) => {
+                let bits = if integer < 0 { integer as i64 as u64 } else { integer as u64 };
+                match (kind, spec.alternate) {
+                    (Kind::LowerHex, false) => format!("{:x}", bits),
+                    (Kind::LowerHex, true) => format!("{:#x}", bits),
+                    (Kind::UpperHex, false) => format!("{:X}", bits),
+                    (Kind::UpperHex, true) => format!("{:#X}", bits),
+                    (Kind::Octal, false) => format!("{:o}", bits),
+                    (Kind::Octal, true) => format!("{:#o}", bits),
+                    (_, false) => format!("{:b}", bits),
+                    (_, true) => format!("{:#b}", bits),
+                }
+            }
+            None => return Err(format!("{} can't be formatted as {}", value, kind.name())),
+        },
+    };
+
+    let body = if spec.plus && numeric && !body.starts_with('-') { format!("+{}", body) } else { body };
+    let len = body.chars().count();
+    let padding = width.unwrap_or(0).saturating_sub(len);
+
+    if spec.zero && numeric {
+        // zeros go after the sign and the `0x`, `0o` or `0b` prefix
+        let sign = if body.starts_with(['+', '-'].as_ref()) { 1 } else { 0 };
+        let radix = matches!(spec.kind, Kind::LowerHex | Kind::UpperHex | Kind::Octal | Kind::Binary);
+        let prefix = if spec.alternate && radix { sign + 2 } else { sign };
+        result.push_str(&body[..prefix]);
+        result.extend((0..padding).map(|_| '0'));
+        result.push_str(&body[prefix..]);
+        return Ok(());
+    }
+
+    let default = if numeric { Align::Right } else { Align::Left };
+    let (before, after) = match spec.align.unwrap_or(default) {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+
+    result.extend((0..before).map(|_| spec.fill));
+    result.push_str(&body);
+    result.extend((0..after).map(|_| spec.fill));
+    Ok(())
+}
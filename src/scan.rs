@@ -0,0 +1,170 @@
+//! Types used by [`ex_scan!`](../macro.ex_scan.html).
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// Error returned by [`ex_scan!`](../macro.ex_scan.html) when the input doesn't match the template.
+///
+/// Positions are byte offsets in the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScanError {
+    /// The input doesn't contain the text `expected` of the template at `position`.
+    Mismatch { expected: &'static str, position: usize },
+    /// The input continues at `position` after the end of the template.
+    Trailing { position: usize },
+    /// The `text` at `position` can't be converted for `placeholder`, as written in the template.
+    Invalid {
+        placeholder: &'static str,
+        position: usize,
+        text: String,
+        message: String,
+    },
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScanError::Mismatch { expected, position } => {
+                write!(f, "expected `{}` at byte {}", expected, position)
+            }
+            ScanError::Trailing { position } => write!(f, "unexpected input at byte {}", position),
+            ScanError::Invalid { placeholder, position, text, message } => write!(
+                f,
+                "invalid value {:?} for `{}` at byte {}: {}",
+                text, placeholder, position, message
+            ),
+        }
+    }
+}
+
+impl Error for ScanError {}
+
+/// Conversion from a string of digits in a radix, used by [`ex_scan!`](../macro.ex_scan.html)
+/// for the `x`, `X`, `o` and `b` format specs.
+pub trait FromStrRadix: Sized {
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, ParseIntError>;
+}
+
+macro_rules! from_str_radix {
+    ($($t:ty)*) => {
+        $(
+            impl FromStrRadix for $t {
+                fn from_str_radix(src: &str, radix: u32) -> Result<Self, ParseIntError> {
+                    <$t>::from_str_radix(src, radix)
+                }
+            }
+        )*
+    };
+}
+
+from_str_radix!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+// walks the input for the code generated by `ex_scan!`
+#[doc(hidden)]
+pub struct Scanner<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Scanner { input, position: 0 }
+    }
+
+    pub fn literal(&mut self, expected: &'static str) -> Result<(), ScanError> {
+        if self.input[self.position..].starts_with(expected) {
+            self.position += expected.len();
+            Ok(())
+        } else {
+            Err(ScanError::Mismatch { expected, position: self.position })
+        }
+    }
+
+    // captures the input up to the first `next`, or all of it if `next` is empty
+    pub fn until(&mut self, placeholder: &'static str, next: &'static str) -> Result<Capture<'a>, ScanError> {
+        let rest = &self.input[self.position..];
+        let end = match rest.find(next) {
+            Some(end) if !next.is_empty() => end,
+            Some(_) => rest.len(),
+            None => return Err(ScanError::Mismatch { expected: next, position: self.input.len() }),
+        };
+
+        Ok(self.capture(placeholder, end, false))
+    }
+
+    // captures `width` characters without the padding around them
+    pub fn width(&mut self, placeholder: &'static str, width: usize) -> Result<Capture<'a>, ScanError> {
+        let rest = &self.input[self.position..];
+        let end = match rest.char_indices().nth(width) {
+            Some((end, _)) => end,
+            None if rest.chars().count() == width => rest.len(),
+            None => {
+                let capture = self.capture(placeholder, rest.len(), false);
+                return Err(capture.invalid(format!("expected {} characters", width)));
+            }
+        };
+
+        Ok(self.capture(placeholder, end, true))
+    }
+
+    pub fn finish(self) -> Result<(), ScanError> {
+        if self.position == self.input.len() {
+            Ok(())
+        } else {
+            Err(ScanError::Trailing { position: self.position })
+        }
+    }
+
+    fn capture(&mut self, placeholder: &'static str, len: usize, trim: bool) -> Capture<'a> {
+        let text = &self.input[self.position..self.position + len];
+        let capture = if trim {
+            let start = text.len() - text.trim_start().len();
+            Capture { placeholder, position: self.position + start, text: text.trim() }
+        } else {
+            Capture { placeholder, position: self.position, text }
+        };
+
+        self.position += len;
+        capture
+    }
+}
+
+// the text of the input for a placeholder
+#[doc(hidden)]
+pub struct Capture<'a> {
+    placeholder: &'static str,
+    position: usize,
+    text: &'a str,
+}
+
+impl Capture<'_> {
+    pub fn parse<T>(self) -> Result<T, ScanError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.text.parse().map_err(|error| self.invalid(error))
+    }
+
+    // `prefix` is required when it isn't empty, like `0x` for `{:#x value}`, in either case since
+    // `format!("{:#X}", value)` writes `0x` too
+    pub fn parse_radix<T: FromStrRadix>(self, radix: u32, prefix: &'static str) -> Result<T, ScanError> {
+        match self.text.get(..prefix.len()) {
+            Some(head) if head.eq_ignore_ascii_case(prefix) => {
+                T::from_str_radix(&self.text[prefix.len()..], radix).map_err(|error| self.invalid(error))
+            }
+            _ => Err(self.invalid(format!("missing prefix `{}`", prefix))),
+        }
+    }
+
+    fn invalid(&self, message: impl Display) -> ScanError {
+        ScanError::Invalid {
+            placeholder: self.placeholder,
+            position: self.position,
+            text: self.text.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Types used by [`ex_sql!`](../macro.ex_sql.html).
+
+/// Query returned by [`ex_sql!`](../macro.ex_sql.html).
+///
+/// `sql` is the query with a placeholder in place of each embedded expression, and `params`
+/// is a tuple with a reference to the value of each parameter in the same order.
+/// The values are never written into `sql`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Query<P> {
+    pub sql: &'static str,
+    pub params: P,
+}
+
+#[cfg(feature = "rusqlite")]
+impl<P: rusqlite::Params> Query<P> {
+    /// Executes the query on `conn` with the parameters bound.
+    ///
+    /// Same as [`Connection::execute`](https://docs.rs/rusqlite/0.31/rusqlite/struct.Connection.html#method.execute).
+    /// Requires the `rusqlite` feature.
+    pub fn execute(self, conn: &rusqlite::Connection) -> rusqlite::Result<usize> {
+        conn.execute(self.sql, self.params)
+    }
+
+    /// Executes the query on `conn` with the parameters bound and maps the first row with `f`.
+    ///
+    /// Same as [`Connection::query_row`](https://docs.rs/rusqlite/0.31/rusqlite/struct.Connection.html#method.query_row).
+    /// Requires the `rusqlite` feature.
+    pub fn query_row<T, F>(self, conn: &rusqlite::Connection, f: F) -> rusqlite::Result<T>
+    where
+        F: FnOnce(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    {
+        conn.query_row(self.sql, self.params, f)
+    }
+}